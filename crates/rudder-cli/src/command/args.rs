@@ -0,0 +1,171 @@
+use std::net::IpAddr;
+
+use anyhow::{Result, bail};
+use clap::{Args, ValueEnum};
+
+use rudder_core::source::InterfaceSource;
+
+use crate::config::{
+    ConsensusPolicyConfig, DnsServiceConfig, GatewayProtocolConfig, InterfaceScopeConfig,
+    SourceConfig,
+};
+
+/// The kind of source to get an external IP address from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SourceKind {
+    /// Asks the gateway / router, through uPnP IGD, PCP, or NAT-PMP
+    Gateway,
+    /// Asks the gateway / router through uPnP IGD only
+    Upnp,
+    /// Asks a "what is my IP" HTTP echo service
+    Http,
+    /// Uses the addresses assigned to local network interfaces
+    Interface,
+    /// Runs a command, and reads addresses from its output
+    Command,
+    /// Sends STUN binding requests to public STUN servers
+    Stun,
+    /// Sends "what is my IP" queries to public DNS services
+    Dns,
+    /// Uses the local address of the default route
+    Route,
+}
+
+/// Arguments for selecting the source of the external IP address
+#[derive(Debug, Clone, Args)]
+pub struct SourceArgs {
+    /// The sources to get the external IP address from, queried at the same time
+    #[clap(
+        long,
+        value_enum,
+        env = "RUDDER_SOURCE",
+        value_delimiter = ',',
+        default_value = "gateway"
+    )]
+    pub source: Vec<SourceKind>,
+    /// How to decide on an address when more than one source is given
+    #[clap(long, value_enum, env = "RUDDER_SOURCE_POLICY")]
+    pub source_policy: Option<ConsensusPolicyConfig>,
+    /// The gateway protocols to try in order, for the "gateway" source
+    #[clap(
+        long,
+        value_enum,
+        env = "RUDDER_SOURCE_PROTOCOLS",
+        value_delimiter = ','
+    )]
+    pub source_protocol: Vec<GatewayProtocolConfig>,
+    /// The address of the gateway for PCP and NAT-PMP, for the "gateway" source
+    #[clap(long, env = "RUDDER_SOURCE_GATEWAY")]
+    pub source_gateway: Option<IpAddr>,
+    /// The URL of the echo service to use, for the "http" source
+    #[clap(long, env = "RUDDER_SOURCE_URL")]
+    pub source_url: Option<String>,
+    /// The name of the network interface to use, for the "interface" source
+    #[clap(long, env = "RUDDER_SOURCE_INTERFACE")]
+    pub source_interface: Option<String>,
+    /// The scope that addresses must have, for the "interface" source
+    #[clap(long, value_enum, env = "RUDDER_SOURCE_SCOPE")]
+    pub source_scope: Option<InterfaceScopeConfig>,
+    /// The shell command to run, for the "command" source
+    #[clap(long, env = "RUDDER_SOURCE_COMMAND")]
+    pub source_command: Option<String>,
+    /// The STUN servers to use, as host:port pairs, for the "stun" source
+    #[clap(long, env = "RUDDER_SOURCE_STUN_SERVERS", value_delimiter = ',')]
+    pub source_stun_server: Vec<String>,
+    /// The DNS services to query in order, for the "dns" source
+    #[clap(
+        long,
+        value_enum,
+        env = "RUDDER_SOURCE_DNS_SERVICES",
+        value_delimiter = ','
+    )]
+    pub source_dns_service: Vec<DnsServiceConfig>,
+    /// The DNS resolvers to query, as host:port pairs, for the "dns" source
+    #[clap(long, env = "RUDDER_SOURCE_DNS_RESOLVERS", value_delimiter = ',')]
+    pub source_dns_resolver: Vec<String>,
+}
+
+impl SourceArgs {
+    /**
+        Converts the arguments into a validated source configuration.
+    */
+    pub fn to_config(&self) -> Result<SourceConfig> {
+        self.kinds_config(&self.source)
+    }
+
+    /**
+        Converts the arguments into a validated source configuration for the given
+        kinds of sources, instead of the selected ones, using the same options.
+    */
+    pub fn kinds_config(&self, kinds: &[SourceKind]) -> Result<SourceConfig> {
+        let config = match kinds {
+            [kind] => self.kind_config(*kind)?,
+            kinds => SourceConfig::Consensus {
+                policy: self.source_policy,
+                sources: kinds
+                    .iter()
+                    .map(|kind| self.kind_config(*kind))
+                    .collect::<Result<_>>()?,
+            },
+        };
+        if let Err(e) = config.validate() {
+            bail!("invalid source: {e}");
+        }
+        Ok(config)
+    }
+
+    /**
+        Converts the arguments into a source configuration for the given kind of source,
+        using the same options as the selected source.
+    */
+    pub fn kind_config(&self, kind: SourceKind) -> Result<SourceConfig> {
+        Ok(match kind {
+            SourceKind::Gateway => SourceConfig::Gateway {
+                protocols: (!self.source_protocol.is_empty()).then(|| self.source_protocol.clone()),
+                gateway: self.source_gateway,
+                timeout: None,
+            },
+            SourceKind::Upnp => SourceConfig::Upnp { timeout: None },
+            SourceKind::Http => SourceConfig::Http {
+                url: self.source_url.clone(),
+            },
+            SourceKind::Interface => SourceConfig::Interface {
+                name: self.source_interface.clone(),
+                scope: self.source_scope,
+            },
+            SourceKind::Command => {
+                let Some(command) = self.source_command.clone() else {
+                    bail!("the \"command\" source requires --source-command to be given");
+                };
+                SourceConfig::Command { command }
+            }
+            SourceKind::Stun => SourceConfig::Stun {
+                servers: (!self.source_stun_server.is_empty())
+                    .then(|| self.source_stun_server.clone()),
+                timeout: None,
+            },
+            SourceKind::Dns => SourceConfig::Dns {
+                services: (!self.source_dns_service.is_empty())
+                    .then(|| self.source_dns_service.clone()),
+                resolvers: (!self.source_dns_resolver.is_empty())
+                    .then(|| self.source_dns_resolver.clone()),
+                timeout: None,
+            },
+            SourceKind::Route => SourceConfig::Route,
+        })
+    }
+
+    /**
+        Creates an interface source from the arguments, regardless of the selected source.
+    */
+    pub fn to_interface_source(&self) -> InterfaceSource {
+        let mut source = InterfaceSource::new();
+        if let Some(name) = &self.source_interface {
+            source = source.with_name(name);
+        }
+        if let Some(scope) = self.source_scope {
+            source = source.with_scope(scope.into());
+        }
+        source
+    }
+}
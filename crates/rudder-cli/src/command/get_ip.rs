@@ -1,15 +1,14 @@
-use std::{
-    net::{IpAddr, SocketAddr},
-    time::Duration,
-};
+use std::{collections::HashMap, net::IpAddr, time::Duration};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use clap::Parser;
-use igd_next::{SearchOptions, aio::tokio::search_gateway};
-use tokio::time::{MissedTickBehavior, interval};
+use tokio::time::{MissedTickBehavior, interval, timeout};
 
+use rudder_core::source::{AddressClass, IpFamily};
 use rudder_http_client::Client;
 
+use super::args::SourceArgs;
+
 /// Gets the current external IP address for this device
 #[derive(Debug, Clone, Parser)]
 pub struct GetIpCommand {
@@ -22,66 +21,151 @@ pub struct GetIpCommand {
     /// How long before timeout for getting the IP occurs (in seconds)
     #[clap(short, long, default_value_t = 10.0)]
     pub timeout: f64,
+    /// Lists all addresses of local network interfaces, and if they could be used, instead
+    #[clap(long, conflicts_with = "watch")]
+    pub candidates: bool,
+    #[clap(flatten)]
+    pub source: SourceArgs,
 }
 
 impl GetIpCommand {
-    pub async fn run(self, _client: &Client) -> Result<()> {
+    pub async fn run(self, client: &Client) -> Result<()> {
+        if self.candidates {
+            return self.list_candidates().await;
+        }
+
         let interval_dur = Duration::from_secs_f64(self.interval);
         let timeout_dur = Duration::from_secs_f64(self.timeout);
 
+        let config = self.source.to_config()?;
+        let Some(source) = config.build(client) else {
+            bail!("no source was selected");
+        };
+
         // 1. Set up an interval for checking IP address regularly,
         //    if watch mode is not enabled this will fire once
-        //    instantly and we only go through one iteration
+        //    instantly and we only go through one iteration - if
+        //    the source detects changes, we also check on those
         let mut ticker = interval(interval_dur);
         ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut watching = self.watch && source.watches_changes();
+        let mut watch_failed = false;
 
-        let mut last_gate = None::<SocketAddr>;
-        let mut last_ip = None::<IpAddr>;
+        let mut last_ips = HashMap::<IpFamily, IpAddr>::new();
+        let mut last_origins = HashMap::<IpFamily, String>::new();
         loop {
-            ticker.tick().await;
-
-            // 2a. Find the current gateway / router through uPnP
-            let options = SearchOptions {
-                timeout: Some(timeout_dur),
-                ..Default::default()
-            };
-            let gateway = search_gateway(options)
-                .await
-                .context("failed to find gateway / router through uPnP")?;
-            let gate = gateway.addr;
-
-            // 2b. Emit a message if it was found or changed
-            if last_gate.is_none_or(|last| gate != last) {
-                if last_gate.is_some() {
-                    println!("Changed gateway / router: {gate}");
-                } else {
-                    println!("Found gateway / router: {gate}");
+            if watching {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    result = source.changed() => if let Err(e) = result {
+                        eprintln!("Failed to watch for IP address changes, falling back to polling: {e:#}");
+                        watch_failed = true;
+                    },
                 }
+            } else {
+                ticker.tick().await;
             }
 
-            // 3a. Find the current external IP address through the gateway
-            let ip = gateway
-                .get_external_ip()
-                .await
-                .context("failed to get external ip through gateway")?;
+            let mut last_error = None;
+            for family in IpFamily::ALL {
+                // 2. Find the current external IP address through the source,
+                //    not all sources or networks support both address families
+                let result = timeout(timeout_dur, source.get_ip(family))
+                    .await
+                    .with_context(|| format!("timed out while getting external {family}"))
+                    .and_then(|result| {
+                        result.with_context(|| format!("failed to get external {family}"))
+                    });
+                let ip = match result {
+                    Ok(Some(ip)) => ip,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        last_error.replace(e);
+                        continue;
+                    }
+                };
 
-            // 3b. Emit a message if it was found or changed
-            if last_ip.is_none_or(|last| ip != last) {
-                if last_ip.is_some() {
-                    println!("Changed external IP: {ip}");
+                // 3. Emit a message if it was found or changed, together with
+                //    whether it could be published, as gateways behind CGNAT
+                //    or another router report addresses that can not, and
+                //    which source or gateway / router it came from
+                let class = AddressClass::of(ip);
+                let details = if class.is_routable() {
+                    class.to_string()
                 } else {
-                    println!("Found external IP: {ip}");
+                    format!("{class}, not publicly routable")
+                };
+                let origin = source
+                    .origin(family)
+                    .await
+                    .unwrap_or_else(|| config.kind().to_string());
+                let last_origin = last_origins.insert(family, origin.clone());
+                match last_ips.insert(family, ip) {
+                    None => {
+                        println!("Found external {family}: {ip} ({details}) - source: {origin}");
+                    }
+                    Some(last) if last != ip => {
+                        println!("Changed external {family}: {ip} ({details}) - source: {origin}");
+                    }
+                    Some(_) if last_origin.is_some_and(|last| last != origin) => {
+                        println!("Changed source for external {family}: {origin}");
+                    }
+                    Some(_) => {}
                 }
             }
 
-            // 4. Store the last known gateway address and external IP
-            last_gate.replace(gateway.addr);
-            last_ip.replace(ip);
+            // 4. Make sure that the source gave us at least one address
+            if last_ips.is_empty() {
+                match last_error {
+                    Some(e) => return Err(e),
+                    None => bail!("no external IP address was found using the selected source"),
+                }
+            }
 
-            // 5. Keep watching for changes if requested, otherwise exit
+            // 5. Keep watching for changes if requested, otherwise exit - the
+            //    source may only know if it detects changes once it answered
             if !self.watch {
                 break;
             }
+            watching = !watch_failed && source.watches_changes();
+        }
+
+        Ok(())
+    }
+
+    async fn list_candidates(&self) -> Result<()> {
+        let source = self.source.to_interface_source();
+        let addresses = source
+            .addresses()
+            .await
+            .context("failed to list interface addresses")?;
+        if addresses.is_empty() {
+            bail!("no interface addresses were found");
+        }
+
+        for address in &addresses {
+            let mut details = vec![
+                address.scope.to_string(),
+                AddressClass::of(address.ip).to_string(),
+            ];
+            if address.temporary {
+                details.push(String::from("temporary"));
+            }
+            if address.deprecated {
+                details.push(String::from("deprecated"));
+            }
+            let usable = if source.is_candidate(address) {
+                "candidate"
+            } else {
+                "skipped"
+            };
+            println!(
+                "{:<9} {:<12} {:<40} {}",
+                usable,
+                address.interface,
+                address.ip.to_string(),
+                details.join(", ")
+            );
         }
 
         Ok(())
@@ -1,8 +1,12 @@
+use std::process::ExitCode;
+
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use rudder_http_client::Client;
 
+mod args;
 mod get_ip;
+mod serve;
 mod start;
 
 #[derive(Debug, Clone, Parser)]
@@ -13,7 +17,7 @@ pub struct Args {
 }
 
 impl Args {
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(self) -> Result<ExitCode> {
         let client = Client::new();
         self.subcommand.run(&client).await
     }
@@ -23,14 +27,17 @@ impl Args {
 pub enum ArgsSubcommand {
     /// Gets the current external IP address for this device
     GetIp(self::get_ip::GetIpCommand),
+    /// Starts a DDNS web server, for use with "custom" DDNS providers
+    Serve(self::serve::ServeCommand),
     /// Starts the DDNS service using the given provider
     Start(self::start::StartCommand),
 }
 
 impl ArgsSubcommand {
-    pub async fn run(self, client: &Client) -> Result<()> {
+    pub async fn run(self, client: &Client) -> Result<ExitCode> {
         match self {
-            Self::GetIp(cmd) => cmd.run(client).await,
+            Self::GetIp(cmd) => cmd.run(client).await.map(|()| ExitCode::SUCCESS),
+            Self::Serve(cmd) => cmd.run(client).await.map(|()| ExitCode::SUCCESS),
             Self::Start(cmd) => cmd.run(client).await,
         }
     }
@@ -0,0 +1,141 @@
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use axum::{Router, extract::State, http::StatusCode, routing::any};
+use clap::Parser;
+use tokio::net::TcpListener;
+
+use rudder_core::{
+    Updater,
+    provider::{CloudflareProvider, DnsRecordChange, Ownership, RecordOptions},
+    source::IpFamily,
+};
+use rudder_extractors::{BasicAuth, Hostname, IpVariant};
+use rudder_http_client::{Client, CloudflareApiError};
+
+use crate::config::validate_owner_id;
+
+/// Starts a DDNS web server, for use with "custom" DDNS providers
+#[derive(Debug, Clone, Parser)]
+pub struct ServeCommand {
+    /// The address to bind the web server to
+    #[clap(
+        short,
+        long,
+        env = "RUDDER_SERVE_ADDRESS",
+        default_value = "0.0.0.0:8080"
+    )]
+    pub address: SocketAddr,
+    /// Whether to take over existing A / AAAA records that are not owned by rudder
+    #[clap(long, env = "RUDDER_SERVE_ADOPT")]
+    pub adopt: bool,
+    /// The owner to mark records with, which should be unique for every instance managing the same zone
+    #[clap(long, env = "RUDDER_OWNER_ID")]
+    pub owner_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ServeState {
+    client: Client,
+    ownership: Ownership,
+}
+
+impl ServeCommand {
+    pub async fn run(self, client: &Client) -> Result<()> {
+        let ownership = match &self.owner_id {
+            Some(owner_id) => {
+                validate_owner_id(owner_id).context("invalid owner id")?;
+                Ownership::new(owner_id)
+            }
+            None => Ownership::default(),
+        };
+
+        let listener = TcpListener::bind(self.address)
+            .await
+            .with_context(|| format!("failed to bind web server to '{}'", self.address))?;
+
+        tracing::info!(
+            "Listening for dynamic DNS requests on '{}'",
+            listener.local_addr()?
+        );
+
+        let state = ServeState {
+            client: client.clone(),
+            ownership: ownership.with_adopt(self.adopt),
+        };
+        let router = Router::new().fallback(any(root)).with_state(state);
+
+        axum::serve(listener, router)
+            .await
+            .context("web server encountered an error")
+    }
+}
+
+async fn root(
+    State(ServeState { client, ownership }): State<ServeState>,
+    auth: BasicAuth<(String, String)>,
+    name: Hostname,
+    ip: IpVariant,
+) -> Result<String, (StatusCode, String)> {
+    let (_email, token) = auth.into_inner();
+
+    // 1. Resolve the IP to use, fetching it ourselves if necessary
+    let ip = match ip {
+        IpVariant::Ip(ip) | IpVariant::Auto(ip) => ip,
+        IpVariant::Fetch => client
+            .ip_api()
+            .get_external_ip()
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{e:#}")))?,
+    };
+
+    // 2. Make sure we got a valid API token to use
+    let cf = client
+        .cloudflare(token)
+        .map_err(|e| (StatusCode::UNAUTHORIZED, format!("{e:#}")))?;
+    cf.verify_token()
+        .await
+        .context("failed to verify given api token")
+        .map_err(|e| (verify_status(&e), format!("{e:#}")))?;
+
+    // 3. Find the zone and update the record within it, never changing
+    //    records that are not owned by rudder unless adopting them
+    let provider = CloudflareProvider::discover(cf)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    let options = RecordOptions {
+        ownership: Some(ownership),
+        ..RecordOptions::default()
+    };
+    let reconciled = Updater::new(ip, provider, name.to_string())
+        .with_options(options)
+        .with_families([IpFamily::from(ip)])
+        .reconcile()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{e:#}")))?;
+    let change = reconciled
+        .first()
+        .map_or(DnsRecordChange::Unchanged, |r| r.change);
+
+    Ok(format!(
+        "{change}!\
+		\n- Hostname: {name}\
+		\n- IP: {ip}",
+    ))
+}
+
+/**
+    Gets the status to respond with when verifying the API token failed - only
+    errors caused by the token itself are unauthorized, since others, such as
+    Cloudflare not being reachable, are not the fault of the client.
+*/
+fn verify_status(error: &anyhow::Error) -> StatusCode {
+    if error
+        .downcast_ref::<CloudflareApiError>()
+        .is_some_and(CloudflareApiError::is_auth)
+    {
+        StatusCode::UNAUTHORIZED
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
+}
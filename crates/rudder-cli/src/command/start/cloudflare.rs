@@ -1,18 +1,29 @@
-use std::{net::IpAddr, time::Duration};
+use std::{process::ExitCode, time::Duration};
 
 use anyhow::{Context, Result, bail};
 use clap::Parser;
-use igd_next::{SearchOptions, aio::tokio::search_gateway};
-use tokio::time::{MissedTickBehavior, interval};
 
+use rudder_core::{
+    Updater,
+    provider::{CloudflareProvider, Ownership, RecordOptions},
+    source::{IpFamily, IpSource, RoutableSource, SplitSource},
+};
 use rudder_extractors::Hostname;
-use rudder_http_client::{
-    Client,
-    models::cloudflare::{CloudflareDnsRecord, CloudflareDnsRecordKind},
+use rudder_http_client::{Client, CloudflareClient};
+
+use super::once::{RunOptions, run_once};
+use crate::{
+    command::args::{SourceArgs, SourceKind},
+    config::{
+        AttributePolicyConfig, DuplicatePolicyConfig, SourceConfig, validate_owner_id,
+        validate_tags, validate_ttl,
+    },
+    state::SharedState,
 };
 
 /// Starts the DDNS service using the Cloudflare provider
 #[derive(Debug, Clone, Parser)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct CloudflareCommand {
     /// The API token (not key) to use for Cloudflare API authentication
     #[clap(long, env = "CLOUDFLARE_API_TOKEN")]
@@ -20,118 +31,218 @@ pub struct CloudflareCommand {
     /// The hostname to use for the DDNS service
     #[clap(long, env = "CLOUDFLARE_HOSTNAME")]
     pub hostname: Hostname,
+    /// The ID of the zone that the hostname is in, found using the API token if not given
+    #[clap(long, env = "CLOUDFLARE_ZONE_ID")]
+    pub zone_id: Option<String>,
+    /// Whether to also keep an AAAA record up to date, using the IPv6 source
+    #[clap(long, env = "CLOUDFLARE_IPV6")]
+    pub ipv6: bool,
+    /// Whether to delete A / AAAA records when there is no address for them, such as when IPv6 connectivity goes away
+    #[clap(long, env = "CLOUDFLARE_DELETE_MISSING")]
+    pub delete_missing: bool,
+    /// The TTL of the A / AAAA records in seconds, where 1 is automatic, the default
+    #[clap(long, env = "CLOUDFLARE_TTL")]
+    pub ttl: Option<u32>,
+    /// Whether the A / AAAA records are proxied through Cloudflare, not proxied by default
+    #[clap(long, env = "CLOUDFLARE_PROXIED")]
+    pub proxied: Option<bool>,
+    /// The comment for the A / AAAA records, next to the owner marker
+    #[clap(long, env = "CLOUDFLARE_COMMENT")]
+    pub comment: Option<String>,
+    /// A tag for the A / AAAA records, can be given multiple times, or separated by commas
+    #[clap(
+        long = "tag",
+        value_name = "TAG",
+        env = "CLOUDFLARE_TAGS",
+        value_delimiter = ','
+    )]
+    pub tags: Vec<String>,
+    /// Whether to also change the TTL, proxied status, comment, and tags of existing records, preserving them by default
+    #[clap(long, value_enum, env = "CLOUDFLARE_ATTRIBUTES")]
+    pub attributes: Option<AttributePolicyConfig>,
+    /// What to do when there are several A / AAAA records for the hostname, updating all of them by default
+    #[clap(long, value_enum, env = "CLOUDFLARE_DUPLICATES")]
+    pub duplicates: Option<DuplicatePolicyConfig>,
+    /// Whether to take over existing A / AAAA records for the hostname that are not owned by rudder
+    #[clap(long, env = "CLOUDFLARE_ADOPT")]
+    pub adopt: bool,
+    /// The owner to mark records with, which should be unique for every instance managing the same zone
+    #[clap(long, env = "RUDDER_OWNER_ID")]
+    pub owner_id: Option<String>,
+    /// How often to check for IP address changes (in seconds)
+    #[clap(long, env = "RUDDER_INTERVAL", default_value_t = 15.0)]
+    pub interval: f64,
+    /// How often to check for IP address changes, when they are detected as they happen (in seconds)
+    #[clap(long, env = "RUDDER_FALLBACK_INTERVAL", default_value_t = 300.0)]
+    pub fallback_interval: f64,
+    /// Whether to publish addresses that are not publicly routable, such as private or CGNAT addresses
+    #[clap(
+        long,
+        env = "CLOUDFLARE_ALLOW_NON_ROUTABLE",
+        conflicts_with = "fallback_source"
+    )]
+    pub allow_non_routable: bool,
+    /// The source to use when a source returns an address that is not publicly routable
+    #[clap(long, value_enum, env = "RUDDER_FALLBACK_SOURCE")]
+    pub fallback_source: Option<SourceKind>,
+    /// The sources for the IPv6 address, with the same options as for IPv4, the default route by default
+    #[clap(
+        long,
+        value_enum,
+        env = "RUDDER_IPV6_SOURCE",
+        value_delimiter = ',',
+        requires = "ipv6"
+    )]
+    pub ipv6_source: Vec<SourceKind>,
+    /// The source for the IPv4 address
+    #[clap(flatten)]
+    pub source: SourceArgs,
 }
 
 impl CloudflareCommand {
-    pub async fn run(self, client: &Client) -> Result<()> {
+    pub async fn run(self, client: &Client, options: &RunOptions) -> Result<ExitCode> {
         tracing::info!(
             "Starting up Cloudflare DDNS service for hostname '{}'",
             self.hostname
         );
 
-        // 1. Make sure we got a valid API token to use
+        // 1. Make sure we got a valid API token, IP source, and intervals to use
+        let source_v4 = self.source.to_config()?;
+        let source_v6 = if self.ipv6_source.is_empty() {
+            SourceConfig::Route
+        } else {
+            self.source.kinds_config(&self.ipv6_source)?
+        };
+        let fallback = match self.fallback_source {
+            Some(kind) => Some(self.source.kind_config(kind)?),
+            None => None,
+        };
+        let interval = parse_interval("interval", self.interval)?;
+        let fallback_interval = parse_interval("fallback interval", self.fallback_interval)?;
+        if let Some(ttl) = self.ttl {
+            validate_ttl(ttl).context("invalid ttl")?;
+        }
+        validate_tags(&self.tags).context("invalid tags")?;
+        let ownership = match &self.owner_id {
+            Some(owner_id) => {
+                validate_owner_id(owner_id).context("invalid owner id")?;
+                Ownership::new(owner_id)
+            }
+            None => Ownership::default(),
+        };
         let cf = client.cloudflare(self.token)?;
-        cf.verify_token()
-            .await
-            .context("failed to verify given api token")?;
-        tracing::info!("Verified API token successfully");
 
-        // 2. Extract the single zone that the API token should be assigned to
-        let mut zones = cf
-            .list_zones()
-            .await
-            .context("failed to list zones for given api token")?;
-        if zones.is_empty() {
-            bail!("given api token is not assigned to any zones");
-        } else if zones.len() > 1 {
-            bail!("given api token is assigned to multiple zones");
+        // 2. Verify the token, and find the zone that the hostname is in, retrying
+        //    for as long as Cloudflare can not be reached
+        let hostname = self.hostname.to_string();
+        let state = options.load_state();
+        let provider = setup_provider(
+            &cf,
+            self.zone_id.as_deref(),
+            &[&hostname],
+            options,
+            &state,
+            "set up Cloudflare",
+        )
+        .await?;
+
+        // 3. Keep the DNS records up to date with the external IPs, checking them
+        //    regularly, or only once, starting from what was published last time
+        let families = if self.ipv6 {
+            vec![IpFamily::V4, IpFamily::V6]
+        } else {
+            vec![IpFamily::V4]
+        };
+        let build = |config: &SourceConfig| {
+            let source = config.build(client)?;
+            if self.allow_non_routable {
+                return Some(source);
+            }
+            let mut source = RoutableSource::new(source);
+            if let Some(fallback) = fallback.as_ref().and_then(|f| f.build(client)) {
+                source = source.with_fallback(fallback);
+            }
+            Some(Box::new(source) as Box<dyn IpSource>)
+        };
+        let source = SplitSource::new(build(&source_v4), build(&source_v6));
+        let record_options = RecordOptions {
+            ttl: self.ttl,
+            proxied: self.proxied,
+            comment: self.comment,
+            tags: self.tags,
+            attributes: self.attributes.map(Into::into).unwrap_or_default(),
+            duplicates: self.duplicates.map(Into::into).unwrap_or_default(),
+            ownership: Some(ownership.with_adopt(self.adopt)),
+        };
+        let updater = Updater::new(source, options.provider(provider), hostname)
+            .with_options(record_options)
+            .with_families(families)
+            .with_delete_missing(self.delete_missing)
+            .with_fallback_interval(fallback_interval);
+        let mut updater = state.attach(updater);
+        if options.once {
+            return run_once(vec![updater]).await;
         }
-        let zone = zones.pop().unwrap();
+        updater.run(interval).await?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/**
+    Sets up a Cloudflare provider for the given hostnames - verifying the API token,
+    and finding the zone that each of the hostnames is in, out of the zones that the
+    token is assigned to. Zones that were remembered for all of the hostnames, or a
+    given zone ID, are used instead of listing the zones again.
+*/
+pub(super) async fn setup_provider(
+    cf: &CloudflareClient,
+    zone_id: Option<&str>,
+    hostnames: &[&str],
+    options: &RunOptions,
+    state: &SharedState,
+    what: &str,
+) -> Result<CloudflareProvider> {
+    let remembered = hostnames
+        .iter()
+        .map(|hostname| state.zone(hostname))
+        .collect::<Option<Vec<_>>>()
+        .filter(|zones| !zones.is_empty());
+
+    // 1. Verify the token, and get the zones to manage records in
+    let provider = options
+        .setup(what, || async {
+            cf.verify_token()
+                .await
+                .context("failed to verify given api token")?;
+            tracing::info!("Verified API token successfully");
+            match (zone_id, &remembered) {
+                (Some(zone_id), _) => Ok(CloudflareProvider::with_zone_id(cf.clone(), zone_id)),
+                (None, Some(zones)) => Ok(zones.iter().skip(1).cloned().fold(
+                    CloudflareProvider::new(cf.clone(), zones[0].clone()),
+                    CloudflareProvider::with_zone,
+                )),
+                (None, None) => CloudflareProvider::discover(cf.clone()).await,
+            }
+        })
+        .await?;
+
+    // 2. Make sure that all of the hostnames are in one of the zones
+    for hostname in hostnames {
+        let zone = provider.zone_for(hostname)?;
         tracing::info!(
+            hostname = %hostname,
             id = %zone.id,
             name = %zone.name,
-            "Found assigned zone successfully",
+            "Found zone for hostname successfully",
         );
+        state.set_zone(hostname, zone);
+    }
+    Ok(provider)
+}
 
-        // 3. Set up an interval for checking IP address regularly
-        let mut ticker = interval(Duration::from_secs_f64(15.0));
-        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
-
-        let mut last_ip = None::<IpAddr>;
-        loop {
-            ticker.tick().await;
-
-            // 4. Find the current gateway / router through uPnP, then external IP address
-            let gateway = search_gateway(SearchOptions::default())
-                .await
-                .context("failed to find gateway / router through uPnP")?;
-            let ip = gateway
-                .get_external_ip()
-                .await
-                .context("failed to get external ip through gateway")?;
-
-            // 5. Update the DNS record if the IP has changed
-            if last_ip.is_none_or(|last| ip != last) {
-                last_ip.replace(ip);
-
-                tracing::info!(ip = %ip, "Updating DNS records with current IP");
-
-                let desired_kind = CloudflareDnsRecordKind::from(ip);
-                let desired_name = self.hostname.to_string();
-
-                // 5a. Look for existing DNS record, to see if we should update instead of creating new
-                let existing_records = cf
-                    .list_dns_records(&zone.id)
-                    .await
-                    .context("failed to fetch current dns records")?;
-                let existing_record = existing_records
-                    .into_iter()
-                    .find(|record| record.name == desired_name && record.kind == desired_kind);
-
-                // 5b. Update or create the record
-                if let Some(existing) = existing_record {
-                    if existing.content == ip.to_string() {
-                        tracing::info!("No DNS record changes necessary");
-                        continue;
-                    }
-
-                    tracing::info!(
-                        kind = ?desired_kind,
-                        name = %desired_name,
-                        content = %ip,
-                        "Updating existing DNS record"
-                    );
-
-                    let mut record = existing.clone();
-                    record.content = ip.to_string();
-
-                    cf.update_dns_record(&zone.id, &existing.id, record)
-                        .await
-                        .context("failed to update dns record")?;
-
-                    tracing::info!("Updated existing DNS record successfully");
-                } else {
-                    tracing::info!(
-                        kind = ?desired_kind,
-                        name = %desired_name,
-                        content = %ip,
-                        "Creating new DNS record"
-                    );
-
-                    let record = CloudflareDnsRecord {
-                        kind: desired_kind,
-                        name: desired_name,
-                        content: ip.to_string(),
-                        ..Default::default()
-                    };
-
-                    cf.create_dns_record(&zone.id, record)
-                        .await
-                        .context("failed to create dns record")?;
-
-                    tracing::info!("Created new DNS record successfully");
-                }
-            }
-        }
+fn parse_interval(name: &str, seconds: f64) -> Result<Duration> {
+    if !seconds.is_finite() || seconds <= 0.0 {
+        bail!("{name} must be a positive number of seconds");
     }
+    Ok(Duration::from_secs_f64(seconds))
 }
@@ -1,32 +1,166 @@
-use anyhow::Result;
+use std::{collections::HashMap, path::PathBuf, process::ExitCode, sync::Arc};
+
+use anyhow::{Context, Result, bail};
 use clap::{Parser, Subcommand};
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use rudder_core::{Updater, provider::DnsProvider};
 use rudder_http_client::Client;
 
+use crate::{
+    config::{Config, ProviderConfig},
+    state::State,
+};
+
 mod cloudflare;
+mod once;
+
+use self::{
+    cloudflare::setup_provider,
+    once::{RunOptions, run_once},
+};
 
 /// Starts the DDNS service using the given provider
 #[derive(Debug, Clone, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct StartCommand {
+    /// Path to a configuration file declaring providers and hostnames
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+    /**
+        Runs a single update for each hostname and exits, instead of running forever
+
+        Exits with 0 if no records needed changes, 10 if any records were changed,
+        20 if an IP source failed, 21 if the DNS provider failed, and 22 for errors
+        that retrying will not fix, such as an invalid API token or a missing zone.
+    */
+    #[clap(long, global = true, env = "RUDDER_ONCE")]
+    pub once: bool,
+    /// Whether to only log the changes that would be made to DNS records, without making them
+    #[clap(long, global = true, env = "RUDDER_DRY_RUN")]
+    pub dry_run: bool,
+    /// Whether to remember zones, records, and the last published IP addresses between runs, in `$XDG_STATE_HOME/rudder/state.json`
+    #[clap(long, global = true, env = "RUDDER_STATE")]
+    pub state: bool,
+    /// Path to a file for remembering zones, records, and the last published IP addresses between runs, instead of the default one
+    #[clap(long, global = true, env = "RUDDER_STATE_FILE")]
+    pub state_file: Option<PathBuf>,
     #[clap(subcommand)]
-    pub subcommand: ArgsSubcommand,
+    pub subcommand: Option<ArgsSubcommand>,
 }
 
 impl StartCommand {
-    pub async fn run(self, client: &Client) -> Result<()> {
-        self.subcommand.run(client).await
+    pub async fn run(self, client: &Client) -> Result<ExitCode> {
+        let state_file = match self.state_file {
+            Some(path) => Some(path),
+            None if self.state => Some(State::default_path()?),
+            None => None,
+        };
+        let options = RunOptions {
+            once: self.once,
+            dry_run: self.dry_run,
+            state_file,
+        };
+        let run = async {
+            match (self.config, self.subcommand) {
+                (Some(path), _) => {
+                    let config = Config::load(path)?;
+                    options.finish(run_config(config, client, &options).await)
+                }
+                (None, Some(subcommand)) => options.finish(subcommand.run(client, &options).await),
+                (None, None) => bail!("either a config file or a provider must be given"),
+            }
+        };
+
+        // Mark everything that is logged during a dry run, so that it is never mistaken for real changes
+        if options.dry_run {
+            tracing::warn!("Running in dry run mode, no DNS records will be changed");
+            run.instrument(tracing::info_span!("dry_run")).await
+        } else {
+            run.await
+        }
     }
 }
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum ArgsSubcommand {
     /// Starts the DDNS service using the Cloudflare provider
-    Cloudflare(self::cloudflare::CloudflareCommand),
+    Cloudflare(Box<self::cloudflare::CloudflareCommand>),
 }
 
 impl ArgsSubcommand {
-    pub async fn run(self, client: &Client) -> Result<()> {
+    pub async fn run(self, client: &Client, options: &RunOptions) -> Result<ExitCode> {
         match self {
-            Self::Cloudflare(cmd) => cmd.run(client).await,
+            Self::Cloudflare(cmd) => cmd.run(client, options).await,
         }
     }
 }
+
+async fn run_config(config: Config, client: &Client, options: &RunOptions) -> Result<ExitCode> {
+    tracing::info!(
+        "Starting up DDNS service for {} hostname(s)",
+        config.hostnames.len()
+    );
+
+    // 1. Set up all of the providers, making sure their credentials are valid
+    let state = options.load_state();
+    let mut providers = HashMap::<String, Arc<dyn DnsProvider>>::new();
+    for (name, provider) in &config.providers {
+        let hostnames = config
+            .hostnames
+            .iter()
+            .filter(|hostname| hostname.provider == *name)
+            .map(|hostname| hostname.name.as_str())
+            .collect::<Vec<_>>();
+        match provider {
+            ProviderConfig::Cloudflare(cf_config) => {
+                let cf = client.cloudflare(cf_config.token()?)?;
+                let what = format!("set up provider '{name}'");
+                let zone_id = cf_config.zone_id.as_deref();
+                let provider = setup_provider(&cf, zone_id, &hostnames, options, &state, &what)
+                    .await
+                    .with_context(|| format!("failed to set up provider '{name}'"))?;
+                tracing::info!(
+                    provider = %name,
+                    zones = provider.zones().len(),
+                    "Set up Cloudflare provider successfully",
+                );
+                providers.insert(name.clone(), options.provider(provider));
+            }
+        }
+    }
+
+    // 2. Set up updaters for all of the hostnames, remembering what they last published
+    let updaters = config.hostnames.iter().map(|hostname| {
+        let provider = Arc::clone(&providers[&hostname.provider]);
+        let updater = Updater::new(hostname.source(client), provider, hostname.name.clone())
+            .with_options(hostname.record_options(config.ownership()))
+            .with_families(hostname.families())
+            .with_delete_missing(hostname.delete_missing)
+            .with_fallback_interval(config.fallback_interval());
+        state.attach(updater)
+    });
+    let updaters = updaters.collect::<Vec<_>>();
+    if options.once {
+        return run_once(updaters).await;
+    }
+
+    // 3. Start keeping all of the hostnames up to date, concurrently
+    let interval = config.interval();
+    let mut tasks = JoinSet::new();
+    for mut updater in updaters {
+        let task = async move {
+            let result = updater.run(interval).await;
+            result.with_context(|| format!("failed to update hostname '{}'", updater.hostname()))
+        };
+        tasks.spawn(task.in_current_span());
+    }
+
+    // 4. Wait for any of the updaters to fail
+    while let Some(result) = tasks.join_next().await {
+        result.context("hostname updater panicked")??;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
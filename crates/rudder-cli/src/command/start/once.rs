@@ -0,0 +1,168 @@
+use std::{future::Future, path::PathBuf, process::ExitCode, sync::Arc};
+
+use anyhow::Result;
+
+use rudder_core::{
+    IpSourceError, ReconcileError, Updater,
+    provider::{DnsProvider, DnsRecordChange, DryRunProvider},
+    retry::{Backoff, is_fatal, retry},
+    source::IpSource,
+};
+
+use crate::state::SharedState;
+
+/**
+    The outcome of a single run with `--once`, in order of severity,
+    each of which exits with a distinct exit code.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    /// All records were already up to date - exit code 0
+    Unchanged,
+    /// At least one record was created, updated, or deleted - exit code 10
+    Updated,
+    /// An IP source failed to find an address - exit code 20
+    SourceFailed,
+    /// The DNS provider could not be reached, or failed otherwise - exit code 21
+    ProviderFailed,
+    /// Retrying will not help, such as with an invalid API token or missing zone - exit code 22
+    Fatal,
+}
+
+impl Outcome {
+    /**
+        Gets the outcome for a failed run, based on what caused the error, or
+        the most severe of the errors for each address family that failed.
+    */
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        if let Some(reconcile) = error.downcast_ref::<ReconcileError>() {
+            return reconcile
+                .errors()
+                .map(Self::from_error)
+                .max()
+                .unwrap_or(Self::ProviderFailed);
+        }
+        if is_fatal(error) {
+            Self::Fatal
+        } else if error.downcast_ref::<IpSourceError>().is_some() {
+            Self::SourceFailed
+        } else {
+            Self::ProviderFailed
+        }
+    }
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Unchanged => ExitCode::SUCCESS,
+            Outcome::Updated => ExitCode::from(10),
+            Outcome::SourceFailed => ExitCode::from(20),
+            Outcome::ProviderFailed => ExitCode::from(21),
+            Outcome::Fatal => ExitCode::from(22),
+        }
+    }
+}
+
+/// Options for how to run the DDNS service, shared by all providers
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Whether to run a single reconciliation step for each hostname, and then exit
+    pub once: bool,
+    /// Whether to only log the changes that would be made to DNS records, instead of making them
+    pub dry_run: bool,
+    /// The path to the file for remembering state between runs, if any
+    pub state_file: Option<PathBuf>,
+}
+
+impl RunOptions {
+    /**
+        Loads the state from the state file, if there is one.
+
+        During a dry run, the state file is neither used nor written
+        to, so that all planned changes to the records are shown.
+    */
+    pub fn load_state(&self) -> SharedState {
+        if self.dry_run {
+            return SharedState::load(None);
+        }
+        SharedState::load(self.state_file.clone())
+    }
+
+    /**
+        Prepares a provider for use by updaters, which only
+        logs the changes it would make during a dry run.
+    */
+    pub fn provider(&self, provider: impl DnsProvider + 'static) -> Arc<dyn DnsProvider> {
+        if self.dry_run {
+            Arc::new(DryRunProvider::new(provider))
+        } else {
+            Arc::new(provider)
+        }
+    }
+
+    /**
+        Runs a setup step, such as verifying credentials for a provider. This
+        is retried until it succeeds, unless only running once, where
+        failing fast is more useful than waiting for the next run.
+    */
+    pub async fn setup<T, F, Fut>(&self, what: &str, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.once {
+            operation().await
+        } else {
+            retry(what, Backoff::default(), operation).await
+        }
+    }
+
+    /**
+        Maps the result of a run to an exit code - for `--once`, failures
+        are reported through the exit code instead of being returned.
+    */
+    pub fn finish(&self, result: Result<ExitCode>) -> Result<ExitCode> {
+        match result {
+            Err(e) if self.once => {
+                tracing::error!("{e:#}");
+                Ok(Outcome::from_error(&e).into())
+            }
+            result => result,
+        }
+    }
+}
+
+/**
+    Runs a single reconciliation step for each of the updaters,
+    and returns the exit code for the most severe outcome.
+*/
+pub async fn run_once<S, P>(updaters: Vec<Updater<S, P>>) -> Result<ExitCode>
+where
+    S: IpSource,
+    P: DnsProvider,
+{
+    let mut outcome = Outcome::Unchanged;
+    for mut updater in updaters {
+        // Reconcile the records, continuing with other hostnames on failure
+        match updater.reconcile().await {
+            Ok(results) => {
+                if results
+                    .iter()
+                    .any(|r| r.change != DnsRecordChange::Unchanged)
+                {
+                    outcome = outcome.max(Outcome::Updated);
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    hostname = %updater.hostname(),
+                    "Failed to update DNS records: {e:#}"
+                );
+                outcome = outcome.max(Outcome::from_error(&e));
+            }
+        }
+    }
+
+    Ok(outcome.into())
+}
@@ -0,0 +1,833 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    env,
+    net::IpAddr,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use rudder_core::{
+    provider::{AttributePolicy, DuplicatePolicy, Ownership, RecordOptions},
+    source::{
+        CommandSource, ConsensusPolicy, ConsensusSource, DnsService, DnsSource, GatewayProtocol,
+        GatewaySource, HttpSource, InterfaceScope, InterfaceSource, IpFamily, IpSource,
+        RoutableSource, RouteSource, SplitSource, StunSource, UpnpSource,
+    },
+};
+use rudder_extractors::Hostname;
+use rudder_http_client::Client;
+
+/**
+    A configuration file, declaring any number of DNS providers,
+    and hostnames that should be kept up to date using them.
+
+    # Example
+
+    ```toml
+    interval = 15.0
+    fallback_interval = 300.0
+    owner_id = "home"
+
+    [providers.main]
+    kind = "cloudflare"
+    token_env = "CLOUDFLARE_API_TOKEN"
+
+    [[hostnames]]
+    name = "home.example.com"
+    provider = "main"
+    ttl = 60
+    comment = "Home router"
+    tags = ["ddns", "site:home"]
+    attributes = "enforce"
+    ipv4 = { kind = "gateway", protocols = ["pcp", "natpmp"], timeout = 5.0 }
+    ipv6 = { kind = "route" }
+    fallback = { kind = "stun" }
+    delete_missing = true
+    duplicates = "delete-extras"
+
+    [[hostnames]]
+    name = "office.example.com"
+    provider = "main"
+    ipv4 = { kind = "http", url = "https://ipv4.icanhazip.com" }
+    ipv6 = { kind = "interface", name = "eth0" }
+
+    [[hostnames]]
+    name = "lab.example.com"
+    provider = "main"
+    ipv4 = { kind = "stun", servers = ["stun.cloudflare.com:3478"] }
+    ipv6 = { kind = "stun" }
+
+    [[hostnames]]
+    name = "nas.example.com"
+    provider = "main"
+    ipv4 = { kind = "dns", services = ["opendns", "cloudflare"] }
+    ipv6 = { kind = "dns", services = ["google"] }
+
+    [[hostnames]]
+    name = "cgnat.example.com"
+    provider = "main"
+    ipv4 = { kind = "consensus", policy = "majority", sources = [
+        { kind = "gateway" },
+        { kind = "http" },
+        { kind = "dns" },
+    ] }
+    ```
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// How often to check for IP address changes (in seconds)
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+    /// How often to check for IP address changes when they are detected as they happen (in seconds)
+    #[serde(default = "default_fallback_interval")]
+    pub fallback_interval: f64,
+    /// The owner to mark records with, which should be unique for every instance managing the same zone
+    pub owner_id: Option<String>,
+    #[serde(default)]
+    pub providers: BTreeMap<String, ProviderConfig>,
+    #[serde(default)]
+    pub hostnames: Vec<HostnameConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ProviderConfig {
+    Cloudflare(CloudflareConfig),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloudflareConfig {
+    /// The API token (not key) to use for Cloudflare API authentication
+    pub token: Option<String>,
+    /// The name of an environment variable containing the API token
+    pub token_env: Option<String>,
+    /// The ID of the zone to manage records in, found using the API token if not given
+    pub zone_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostnameConfig {
+    pub name: String,
+    pub provider: String,
+    /// The TTL of records in seconds, where 1 is automatic, the provider default if not given
+    pub ttl: Option<u32>,
+    /// Whether records are proxied through Cloudflare, the provider default if not given
+    pub proxied: Option<bool>,
+    /// The comment for records, next to the owner marker
+    pub comment: Option<String>,
+    /// The tags for records, which are only available on some Cloudflare plans
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether to also change the attributes of existing records, preserving them by default
+    pub attributes: Option<AttributePolicyConfig>,
+    /// The source for the IPv4 address, the gateway / router by default
+    #[serde(default = "default_ipv4_source")]
+    pub ipv4: SourceConfig,
+    /// The source for the IPv6 address, disabled by default
+    #[serde(default = "default_ipv6_source")]
+    pub ipv6: SourceConfig,
+    /// The source to use when a source returns an address that is not publicly routable
+    pub fallback: Option<SourceConfig>,
+    /// Whether to publish addresses that are not publicly routable, such as private addresses
+    #[serde(default)]
+    pub allow_non_routable: bool,
+    /// Whether to delete A / AAAA records when their source has no address
+    #[serde(default)]
+    pub delete_missing: bool,
+    /// What to do when there are several A / AAAA records, updating all of them by default
+    pub duplicates: Option<DuplicatePolicyConfig>,
+    /// Whether to take over existing A / AAAA records that are not owned by rudder
+    #[serde(default)]
+    pub adopt: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SourceConfig {
+    /// Records for this address family are not managed
+    #[serde(rename = "none")]
+    Disabled,
+    /// Asks the gateway / router, trying each of the given protocols in order
+    Gateway {
+        /// The protocols to try, all of them by default
+        protocols: Option<Vec<GatewayProtocolConfig>>,
+        /// The address of the gateway for PCP and NAT-PMP, the default gateway by default
+        gateway: Option<IpAddr>,
+        /// How long before timeout for each protocol occurs (in seconds)
+        timeout: Option<f64>,
+    },
+    Upnp {
+        /// How long before timeout for finding the gateway occurs (in seconds)
+        timeout: Option<f64>,
+    },
+    /// Uses a "what is my IP" HTTP echo service
+    Http {
+        /// The URL of the echo service, icanhazip.com by default
+        url: Option<String>,
+    },
+    /// Uses the addresses assigned to local network interfaces
+    Interface {
+        /// The name of the interface to use, any interface by default
+        name: Option<String>,
+        /// The scope that addresses must have, global by default
+        scope: Option<InterfaceScopeConfig>,
+    },
+    /// Runs a command using the system shell, and reads addresses from its output
+    Command { command: String },
+    /// Sends STUN binding requests to public STUN servers
+    Stun {
+        /// The STUN servers to use, as `host:port` pairs, tried in order
+        servers: Option<Vec<String>>,
+        /// How long before timeout for each server occurs (in seconds)
+        timeout: Option<f64>,
+    },
+    /// Sends "what is my IP" queries to public DNS services
+    Dns {
+        /// The services to query, tried in order, all of them by default
+        services: Option<Vec<DnsServiceConfig>>,
+        /// The resolvers to query, as `host:port` pairs, the public resolvers of each service by default
+        resolvers: Option<Vec<String>>,
+        /// How long before timeout for each resolver occurs (in seconds)
+        timeout: Option<f64>,
+    },
+    /// Uses the local address of the default route
+    Route,
+    /// Queries several sources at the same time, and applies a policy to their answers
+    Consensus {
+        /// How to decide on an address, the first source that found one by default
+        policy: Option<ConsensusPolicyConfig>,
+        /// The sources to query, in order
+        sources: Vec<SourceConfig>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConsensusPolicyConfig {
+    /// Use the address from the first source that found one
+    FirstSuccess,
+    /// Use the address that most sources agree on
+    Majority,
+    /// Use the address only if all sources agree on it
+    AllMustAgree,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicyConfig {
+    /// Update all of the records to point at the same address
+    UpdateAll,
+    /// Update one of the records, and delete all of the others
+    DeleteExtras,
+    /// Change none of the records, and fail until all but one of them are removed
+    Refuse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributePolicyConfig {
+    /// Keep the TTL, proxied status, comment, and tags of existing records
+    Preserve,
+    /// Change the TTL, proxied status, comment, and tags of existing records to the configured ones
+    Enforce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GatewayProtocolConfig {
+    /// uPnP Internet Gateway Device
+    Igd,
+    /// Port Control Protocol
+    Pcp,
+    /// NAT Port Mapping Protocol
+    #[value(name = "natpmp")]
+    NatPmp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsServiceConfig {
+    /// myip.opendns.com, using the OpenDNS resolvers
+    #[value(name = "opendns")]
+    OpenDns,
+    /// whoami.cloudflare, using the Cloudflare resolvers
+    Cloudflare,
+    /// o-o.myaddr.l.google.com, using the Google nameservers
+    Google,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceScopeConfig {
+    /// Globally routable addresses
+    Global,
+    /// Addresses only valid within a site
+    Site,
+}
+
+impl From<InterfaceScopeConfig> for InterfaceScope {
+    fn from(value: InterfaceScopeConfig) -> Self {
+        match value {
+            InterfaceScopeConfig::Global => Self::Global,
+            InterfaceScopeConfig::Site => Self::Site,
+        }
+    }
+}
+
+impl From<ConsensusPolicyConfig> for ConsensusPolicy {
+    fn from(value: ConsensusPolicyConfig) -> Self {
+        match value {
+            ConsensusPolicyConfig::FirstSuccess => Self::FirstSuccess,
+            ConsensusPolicyConfig::Majority => Self::Majority,
+            ConsensusPolicyConfig::AllMustAgree => Self::AllMustAgree,
+        }
+    }
+}
+
+impl From<DuplicatePolicyConfig> for DuplicatePolicy {
+    fn from(value: DuplicatePolicyConfig) -> Self {
+        match value {
+            DuplicatePolicyConfig::UpdateAll => Self::UpdateAll,
+            DuplicatePolicyConfig::DeleteExtras => Self::DeleteExtras,
+            DuplicatePolicyConfig::Refuse => Self::Refuse,
+        }
+    }
+}
+
+impl From<AttributePolicyConfig> for AttributePolicy {
+    fn from(value: AttributePolicyConfig) -> Self {
+        match value {
+            AttributePolicyConfig::Preserve => Self::Preserve,
+            AttributePolicyConfig::Enforce => Self::Enforce,
+        }
+    }
+}
+
+impl From<DnsServiceConfig> for DnsService {
+    fn from(value: DnsServiceConfig) -> Self {
+        match value {
+            DnsServiceConfig::OpenDns => Self::OpenDns,
+            DnsServiceConfig::Cloudflare => Self::Cloudflare,
+            DnsServiceConfig::Google => Self::Google,
+        }
+    }
+}
+
+impl From<GatewayProtocolConfig> for GatewayProtocol {
+    fn from(value: GatewayProtocolConfig) -> Self {
+        match value {
+            GatewayProtocolConfig::Igd => Self::Igd,
+            GatewayProtocolConfig::Pcp => Self::Pcp,
+            GatewayProtocolConfig::NatPmp => Self::NatPmp,
+        }
+    }
+}
+
+impl Config {
+    /**
+        Reads, parses, and validates the configuration file at the given path.
+
+        Any validation errors will be reported all at once,
+        together with the key they were found at.
+    */
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file at '{}'", path.display()))?;
+        let mut config = toml::from_str::<Self>(&contents)
+            .with_context(|| format!("failed to parse config file at '{}'", path.display()))?;
+
+        let errors = config.validate();
+        if !errors.is_empty() {
+            bail!(
+                "invalid config file at '{}':\n- {}",
+                path.display(),
+                errors.join("\n- ")
+            );
+        }
+
+        Ok(config)
+    }
+
+    /**
+        Gets the interval for checking IP address changes.
+    */
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(self.interval)
+    }
+
+    /**
+        Gets the interval for checking IP address changes,
+        for sources that detect changes as they happen.
+    */
+    pub fn fallback_interval(&self) -> Duration {
+        Duration::from_secs_f64(self.fallback_interval)
+    }
+
+    /**
+        Gets the owner to mark records with, which is shared by all hostnames.
+    */
+    pub fn ownership(&self) -> Ownership {
+        self.owner_id
+            .as_ref()
+            .map_or_else(Ownership::default, Ownership::new)
+    }
+
+    fn validate(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !self.interval.is_finite() || self.interval <= 0.0 {
+            errors.push(String::from(
+                "interval: must be a positive number of seconds",
+            ));
+        }
+        if !self.fallback_interval.is_finite() || self.fallback_interval <= 0.0 {
+            errors.push(String::from(
+                "fallback_interval: must be a positive number of seconds",
+            ));
+        }
+
+        if let Some(owner_id) = &self.owner_id
+            && let Err(e) = validate_owner_id(owner_id)
+        {
+            errors.push(format!("owner_id: {e}"));
+        }
+
+        for (name, provider) in &self.providers {
+            match provider {
+                ProviderConfig::Cloudflare(cf) => {
+                    if let Err(e) = cf.token() {
+                        errors.push(format!("providers.{name}: {e}"));
+                    }
+                    if cf.zone_id.as_ref().is_some_and(|id| id.trim().is_empty()) {
+                        errors.push(format!("providers.{name}: zone_id: zone id is empty"));
+                    }
+                }
+            }
+        }
+
+        if self.hostnames.is_empty() {
+            errors.push(String::from(
+                "hostnames: at least one hostname must be declared",
+            ));
+        }
+
+        let mut seen = HashSet::new();
+        for (index, hostname) in self.hostnames.iter_mut().enumerate() {
+            let key = format!("hostnames[{index}]");
+
+            match Hostname::from_str(&hostname.name) {
+                Ok(parsed) => {
+                    hostname.name = parsed.to_string();
+                    if !seen.insert(hostname.name.clone()) {
+                        errors.push(format!(
+                            "{key}.name: hostname '{}' is declared more than once",
+                            hostname.name
+                        ));
+                    }
+                }
+                Err(e) => errors.push(format!(
+                    "{key}.name: invalid hostname '{}': {e}",
+                    hostname.name
+                )),
+            }
+
+            if !self.providers.contains_key(&hostname.provider) {
+                errors.push(format!(
+                    "{key}.provider: unknown provider '{}'",
+                    hostname.provider
+                ));
+            }
+
+            if let Some(ttl) = hostname.ttl
+                && let Err(e) = validate_ttl(ttl)
+            {
+                errors.push(format!("{key}.ttl: {e}"));
+            }
+
+            if let Err(e) = validate_tags(&hostname.tags) {
+                errors.push(format!("{key}.tags: {e}"));
+            }
+
+            if matches!(hostname.ipv4, SourceConfig::Disabled)
+                && matches!(hostname.ipv6, SourceConfig::Disabled)
+            {
+                errors.push(format!(
+                    "{key}: at least one of 'ipv4' or 'ipv6' must have a source"
+                ));
+            }
+
+            for (family, source) in [("ipv4", &hostname.ipv4), ("ipv6", &hostname.ipv6)] {
+                if let Err(e) = source.validate() {
+                    errors.push(format!("{key}.{family}.{e}"));
+                }
+            }
+
+            match &hostname.fallback {
+                Some(_) if hostname.allow_non_routable => errors.push(format!(
+                    "{key}.fallback: is never used when 'allow_non_routable' is set"
+                )),
+                Some(SourceConfig::Disabled) => {
+                    errors.push(format!("{key}.fallback: source must not be 'none'"));
+                }
+                Some(fallback) => {
+                    if let Err(e) = fallback.validate() {
+                        errors.push(format!("{key}.fallback.{e}"));
+                    }
+                }
+                None => {}
+            }
+        }
+
+        errors
+    }
+}
+
+impl CloudflareConfig {
+    /**
+        Gets the API token, either directly or from the environment.
+    */
+    pub fn token(&self) -> Result<String> {
+        let token = match (&self.token, &self.token_env) {
+            (Some(token), None) => token.clone(),
+            (None, Some(var)) => env::var(var).with_context(|| {
+                format!("token_env: failed to read environment variable '{var}'")
+            })?,
+            (Some(_), Some(_)) => bail!("only one of 'token' or 'token_env' may be set"),
+            (None, None) => bail!("one of 'token' or 'token_env' must be set"),
+        };
+        if token.trim().is_empty() {
+            bail!("token: api token is empty");
+        }
+        Ok(token)
+    }
+}
+
+impl HostnameConfig {
+    /**
+        Gets the attributes to use for records of this hostname, and options
+        for handling duplicate records, and records owned by others.
+    */
+    pub fn record_options(&self, ownership: Ownership) -> RecordOptions {
+        RecordOptions {
+            ttl: self.ttl,
+            proxied: self.proxied,
+            comment: self.comment.clone(),
+            tags: self.tags.clone(),
+            attributes: self.attributes.map(Into::into).unwrap_or_default(),
+            duplicates: self.duplicates.map(Into::into).unwrap_or_default(),
+            ownership: Some(ownership.with_adopt(self.adopt)),
+        }
+    }
+
+    /**
+        Gets the address families that have a source, and should be managed.
+    */
+    pub fn families(&self) -> Vec<IpFamily> {
+        let mut families = Vec::new();
+        if !matches!(self.ipv4, SourceConfig::Disabled) {
+            families.push(IpFamily::V4);
+        }
+        if !matches!(self.ipv6, SourceConfig::Disabled) {
+            families.push(IpFamily::V6);
+        }
+        families
+    }
+
+    /**
+        Creates the IP source for both address families of this hostname,
+        which refuses addresses that are not publicly routable unless allowed.
+    */
+    pub fn source(&self, client: &Client) -> Box<dyn IpSource> {
+        let build = |config: &SourceConfig| {
+            let source = config.build(client)?;
+            if self.allow_non_routable {
+                return Some(source);
+            }
+            let mut source = RoutableSource::new(source);
+            if let Some(fallback) = self.fallback.as_ref().and_then(|f| f.build(client)) {
+                source = source.with_fallback(fallback);
+            }
+            Some(Box::new(source) as Box<dyn IpSource>)
+        };
+        Box::new(SplitSource::new(build(&self.ipv4), build(&self.ipv6)))
+    }
+}
+
+impl SourceConfig {
+    /**
+        Validates the options for this source, returning an
+        error message prefixed with the invalid key, if any.
+    */
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Upnp { timeout }
+            | Self::Gateway { timeout, .. }
+            | Self::Stun { timeout, .. }
+            | Self::Dns { timeout, .. }
+                if timeout.is_some_and(|t| !t.is_finite() || t <= 0.0) =>
+            {
+                Err(String::from(
+                    "timeout: must be a positive number of seconds",
+                ))
+            }
+            Self::Gateway {
+                protocols: Some(protocols),
+                ..
+            } if protocols.is_empty() => Err(String::from(
+                "protocols: at least one protocol must be given",
+            )),
+            Self::Stun {
+                servers: Some(servers),
+                ..
+            } if servers.is_empty() => {
+                Err(String::from("servers: at least one server must be given"))
+            }
+            Self::Stun {
+                servers: Some(servers),
+                ..
+            } => match servers.iter().find(|s| !is_host_and_port(s)) {
+                Some(server) => Err(format!(
+                    "servers: must be 'host:port' pairs, got '{server}'"
+                )),
+                None => Ok(()),
+            },
+            Self::Dns {
+                services: Some(services),
+                ..
+            } if services.is_empty() => {
+                Err(String::from("services: at least one service must be given"))
+            }
+            Self::Dns {
+                resolvers: Some(resolvers),
+                ..
+            } if resolvers.is_empty() => Err(String::from(
+                "resolvers: at least one resolver must be given",
+            )),
+            Self::Dns {
+                resolvers: Some(resolvers),
+                ..
+            } => match resolvers.iter().find(|r| !is_host_and_port(r)) {
+                Some(resolver) => Err(format!(
+                    "resolvers: must be 'host:port' pairs, got '{resolver}'"
+                )),
+                None => Ok(()),
+            },
+            Self::Http { url: Some(url) }
+                if !url.starts_with("http://") && !url.starts_with("https://") =>
+            {
+                Err(format!("url: must be an http or https url, got '{url}'"))
+            }
+            Self::Interface {
+                name: Some(name), ..
+            } if name.trim().is_empty() => Err(String::from("name: interface name is empty")),
+            Self::Command { command } if command.trim().is_empty() => {
+                Err(String::from("command: command is empty"))
+            }
+            Self::Consensus { sources, .. } if sources.is_empty() => {
+                Err(String::from("sources: at least one source must be given"))
+            }
+            Self::Consensus { sources, .. } => {
+                for (index, source) in sources.iter().enumerate() {
+                    if matches!(source, Self::Disabled) {
+                        return Err(format!("sources[{index}]: source must not be 'none'"));
+                    }
+                    source
+                        .validate()
+                        .map_err(|e| format!("sources[{index}].{e}"))?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /**
+        Creates the IP source, or `None` if this source is disabled.
+    */
+    pub fn build(&self, client: &Client) -> Option<Box<dyn IpSource>> {
+        match self {
+            Self::Disabled => None,
+            Self::Gateway {
+                protocols,
+                gateway,
+                timeout,
+            } => {
+                let mut source = GatewaySource::new();
+                if let Some(protocols) = protocols {
+                    source = source.with_protocols(protocols.iter().copied().map(Into::into));
+                }
+                if let Some(gateway) = gateway {
+                    source = source.with_gateway(*gateway);
+                }
+                if let Some(timeout) = timeout {
+                    source = source.with_timeout(Duration::from_secs_f64(*timeout));
+                }
+                Some(Box::new(source))
+            }
+            Self::Upnp { timeout } => {
+                let mut source = UpnpSource::new();
+                if let Some(timeout) = timeout {
+                    source = source.with_timeout(Duration::from_secs_f64(*timeout));
+                }
+                Some(Box::new(source))
+            }
+            Self::Http { url } => {
+                let mut source = HttpSource::new(client);
+                if let Some(url) = url {
+                    source = source.with_url(url);
+                }
+                Some(Box::new(source))
+            }
+            Self::Interface { name, scope } => {
+                let mut source = InterfaceSource::new();
+                if let Some(name) = name {
+                    source = source.with_name(name);
+                }
+                if let Some(scope) = scope {
+                    source = source.with_scope((*scope).into());
+                }
+                Some(Box::new(source))
+            }
+            Self::Command { command } => Some(Box::new(CommandSource::shell(command))),
+            Self::Stun { servers, timeout } => {
+                let mut source = StunSource::new();
+                if let Some(servers) = servers {
+                    source = source.with_servers(servers);
+                }
+                if let Some(timeout) = timeout {
+                    source = source.with_timeout(Duration::from_secs_f64(*timeout));
+                }
+                Some(Box::new(source))
+            }
+            Self::Dns {
+                services,
+                resolvers,
+                timeout,
+            } => {
+                let mut source = DnsSource::new();
+                if let Some(services) = services {
+                    source = source.with_services(services.iter().copied().map(Into::into));
+                }
+                if let Some(resolvers) = resolvers {
+                    source = source.with_resolvers(resolvers);
+                }
+                if let Some(timeout) = timeout {
+                    source = source.with_timeout(Duration::from_secs_f64(*timeout));
+                }
+                Some(Box::new(source))
+            }
+            Self::Route => Some(Box::new(RouteSource::new())),
+            Self::Consensus { policy, sources } => {
+                let mut source = ConsensusSource::new(policy.map(Into::into).unwrap_or_default());
+                for (index, config) in sources.iter().enumerate() {
+                    let Some(inner) = config.build(client) else {
+                        continue;
+                    };
+                    // Only number sources when the same kind is used more than once
+                    let kind = config.kind();
+                    let name = if sources.iter().filter(|s| s.kind() == kind).count() > 1 {
+                        format!("{kind}#{}", index + 1)
+                    } else {
+                        kind.to_string()
+                    };
+                    source = source.with_source(name, inner);
+                }
+                Some(Box::new(source))
+            }
+        }
+    }
+
+    /**
+        Gets the kind of this source, as used in the configuration file.
+    */
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Disabled => "none",
+            Self::Gateway { .. } => "gateway",
+            Self::Upnp { .. } => "upnp",
+            Self::Http { .. } => "http",
+            Self::Interface { .. } => "interface",
+            Self::Command { .. } => "command",
+            Self::Stun { .. } => "stun",
+            Self::Dns { .. } => "dns",
+            Self::Route => "route",
+            Self::Consensus { .. } => "consensus",
+        }
+    }
+}
+
+fn is_host_and_port(s: &str) -> bool {
+    s.rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+fn default_interval() -> f64 {
+    15.0
+}
+
+fn default_fallback_interval() -> f64 {
+    300.0
+}
+
+fn default_ipv4_source() -> SourceConfig {
+    SourceConfig::Gateway {
+        protocols: None,
+        gateway: None,
+        timeout: None,
+    }
+}
+
+fn default_ipv6_source() -> SourceConfig {
+    SourceConfig::Disabled
+}
+
+/**
+    Checks that an owner ID can be used to mark records, which
+    means it must be non-empty, and can not contain whitespace.
+*/
+pub fn validate_owner_id(owner_id: &str) -> Result<()> {
+    if owner_id.is_empty() {
+        bail!("owner id is empty");
+    }
+    if owner_id.contains(char::is_whitespace) {
+        bail!("owner id can not contain whitespace");
+    }
+    Ok(())
+}
+
+/**
+    Checks that a TTL is accepted by Cloudflare, which means it must
+    be 1 for automatic, or between 30 seconds and one day.
+*/
+pub fn validate_ttl(ttl: u32) -> Result<()> {
+    if ttl != 1 && !(30..=86400).contains(&ttl) {
+        bail!("must be 1 (automatic) or between 30 and 86400, got {ttl}");
+    }
+    Ok(())
+}
+
+/**
+    Checks that tags can be set on records, which means
+    they must be non-empty, and can not contain whitespace.
+*/
+pub fn validate_tags(tags: &[String]) -> Result<()> {
+    for tag in tags {
+        if tag.is_empty() {
+            bail!("tag is empty");
+        }
+        if tag.contains(char::is_whitespace) {
+            bail!("tag '{tag}' can not contain whitespace");
+        }
+    }
+    Ok(())
+}
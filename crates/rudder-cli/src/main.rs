@@ -1,14 +1,18 @@
+use std::process::ExitCode;
+
 use anyhow::Result;
 use clap::Parser;
 
 mod command;
+mod config;
+mod state;
 mod utils;
 
 use self::command::Args;
 use self::utils::{init_env, init_tracing};
 
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     init_env();
     init_tracing();
     Args::parse().run().await
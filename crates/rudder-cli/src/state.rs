@@ -0,0 +1,342 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use rudder_core::{
+    Updater,
+    provider::{DnsProvider, DnsRecord, DnsRecordKind},
+    source::{IpFamily, IpSource},
+};
+use rudder_http_client::models::cloudflare::CloudflareZone;
+
+/**
+    State that is remembered between runs - the zone that each hostname
+    is in, and the IP addresses and records that were last published for it.
+
+    # Example
+
+    ```json
+    {
+      "hostnames": {
+        "home.example.com": {
+          "zone": {
+            "id": "023e105f4ecef8ad9ca31a8372d0c353",
+            "name": "example.com",
+            "account": {
+              "id": "372e67954025e0ba6aaa6d586b9e0b59",
+              "name": "Example Account"
+            }
+          },
+          "ipv4": "203.0.113.7",
+          "records": {
+            "A": {
+              "id": "372e67954025e0ba6aaa6d586b9e0b59",
+              "ttl": 1,
+              "proxied": false
+            }
+          }
+        }
+      }
+    }
+    ```
+*/
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub hostnames: BTreeMap<String, HostnameState>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostnameState {
+    /// The Cloudflare zone that the hostname is in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<CloudflareZone>,
+    /// The IPv4 address that was last published in the A record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv4: Option<IpAddr>,
+    /// The IPv6 address that was last published in the AAAA record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<IpAddr>,
+    /// The records that were last published, by record kind
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub records: BTreeMap<String, RecordState>,
+}
+
+/// A DNS record that was last published, with its content being the last published address
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordState {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl State {
+    /**
+        Gets the default path for the state file,
+        which is `$XDG_STATE_HOME/rudder/state.json`.
+    */
+    pub fn default_path() -> Result<PathBuf> {
+        let state_home = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => match env::var_os("HOME").filter(|dir| !dir.is_empty()) {
+                Some(home) => PathBuf::from(home).join(".local").join("state"),
+                None => bail!(
+                    "could not find a state directory, neither XDG_STATE_HOME nor HOME is set"
+                ),
+            },
+        };
+        Ok(state_home.join("rudder").join("state.json"))
+    }
+
+    /**
+        Reads and parses the state file at the given path,
+        or returns empty state if it does not exist yet.
+    */
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to read state file at '{}'", path.display()));
+            }
+        };
+        serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse state file at '{}'", path.display()))
+    }
+
+    /**
+        Writes the state file to the given path, creating parent directories if necessary.
+
+        The file is written to a temporary file next to it first, and then
+        renamed, so that it is never left half-written if rudder is stopped.
+    */
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create state directory at '{}'", parent.display())
+            })?;
+        }
+
+        let mut contents =
+            serde_json::to_string_pretty(self).context("failed to serialize state")?;
+        contents.push('\n');
+
+        let mut temp_name = path.as_os_str().to_owned();
+        temp_name.push(".tmp");
+        let temp_path = Path::new(&temp_name);
+        fs::write(temp_path, contents)
+            .with_context(|| format!("failed to write state file at '{}'", temp_path.display()))?;
+        fs::rename(temp_path, path)
+            .with_context(|| format!("failed to replace state file at '{}'", path.display()))
+    }
+
+    /**
+        Seeds the updater with the addresses and records that were last
+        published for its hostname, so that records are only looked up
+        again once the addresses change.
+    */
+    pub fn seed<S, P>(&self, mut updater: Updater<S, P>) -> Updater<S, P>
+    where
+        S: IpSource,
+        P: DnsProvider,
+    {
+        let Some(remembered) = self.hostnames.get(updater.hostname()) else {
+            return updater;
+        };
+        for family in IpFamily::ALL {
+            let Some(ip) = remembered.ip(family) else {
+                continue;
+            };
+            updater = updater.with_last_ip(family, ip);
+            if let Some(record) = remembered.record(updater.hostname(), family, ip) {
+                updater = updater.with_last_record(family, record);
+            }
+        }
+        updater
+    }
+
+    /**
+        Remembers the addresses and records that the updater last published for its hostname.
+    */
+    pub fn remember<S, P>(&mut self, updater: &Updater<S, P>)
+    where
+        S: IpSource,
+        P: DnsProvider,
+    {
+        let remembered = self
+            .hostnames
+            .entry(updater.hostname().to_string())
+            .or_default();
+        for family in IpFamily::ALL {
+            remembered.set_ip(family, updater.last_ip(family));
+            remembered.set_record(family, updater.last_record(family));
+        }
+    }
+}
+
+impl HostnameState {
+    /**
+        Gets the IP address that was last published for the given address family.
+    */
+    pub fn ip(&self, family: IpFamily) -> Option<IpAddr> {
+        match family {
+            IpFamily::V4 => self.ipv4,
+            IpFamily::V6 => self.ipv6,
+        }
+    }
+
+    /**
+        Sets the IP address that was last published for the given address family.
+    */
+    pub fn set_ip(&mut self, family: IpFamily, ip: Option<IpAddr>) {
+        match family {
+            IpFamily::V4 => self.ipv4 = ip,
+            IpFamily::V6 => self.ipv6 = ip,
+        }
+    }
+
+    /**
+        Gets the record that was last published for the given address family,
+        pointing at the given IP address.
+    */
+    pub fn record(&self, hostname: &str, family: IpFamily, ip: IpAddr) -> Option<DnsRecord> {
+        let kind = DnsRecordKind::from(family);
+        let record = self.records.get(&kind.to_string())?;
+        Some(DnsRecord {
+            id: record.id.clone(),
+            kind,
+            name: hostname.to_string(),
+            content: ip.to_string(),
+            ttl: record.ttl,
+            proxied: record.proxied,
+            comment: record.comment.clone(),
+            tags: record.tags.clone(),
+        })
+    }
+
+    /**
+        Sets the record that was last published for the given address family.
+    */
+    pub fn set_record(&mut self, family: IpFamily, record: Option<&DnsRecord>) {
+        let kind = DnsRecordKind::from(family).to_string();
+        match record {
+            Some(record) => {
+                let record = RecordState {
+                    id: record.id.clone(),
+                    ttl: record.ttl,
+                    proxied: record.proxied,
+                    comment: record.comment.clone(),
+                    tags: record.tags.clone(),
+                };
+                self.records.insert(kind, record);
+            }
+            None => {
+                self.records.remove(&kind);
+            }
+        }
+    }
+}
+
+/**
+    The [`State`] for a single run of the DDNS service, shared between
+    all of its updaters, and saved to the state file whenever it changes.
+
+    Without a state file, the state is only kept in memory.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SharedState {
+    path: Option<PathBuf>,
+    state: Arc<Mutex<State>>,
+}
+
+impl SharedState {
+    /**
+        Loads the state from the state file at the given path, if any. The state
+        is only used to skip API calls, so a state file that can not be read
+        is ignored, and replaced once something new is published.
+    */
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let state = match path.as_deref().map(State::load) {
+            Some(Ok(state)) => state,
+            Some(Err(e)) => {
+                tracing::warn!("Ignoring state file: {e:#}");
+                State::default()
+            }
+            None => State::default(),
+        };
+        Self {
+            path,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /**
+        Gets the zone that the given hostname was last found in, if any.
+    */
+    pub fn zone(&self, hostname: &str) -> Option<CloudflareZone> {
+        let state = self.lock();
+        let zone = state.hostnames.get(hostname)?.zone.as_ref()?;
+        // Only trust a remembered zone that the hostname could actually be in
+        let in_zone = hostname == zone.name || hostname.ends_with(&format!(".{}", zone.name));
+        in_zone.then(|| zone.clone())
+    }
+
+    /**
+        Remembers the zone that the given hostname was found in.
+    */
+    pub fn set_zone(&self, hostname: &str, zone: &CloudflareZone) {
+        let mut state = self.lock();
+        state
+            .hostnames
+            .entry(hostname.to_string())
+            .or_default()
+            .zone = Some(zone.clone());
+        self.save(&state);
+    }
+
+    /**
+        Seeds the updater with what was last published for its hostname,
+        and remembers anything it publishes from now on.
+    */
+    pub fn attach<S, P>(&self, updater: Updater<S, P>) -> Updater<S, P>
+    where
+        S: IpSource,
+        P: DnsProvider,
+    {
+        let shared = self.clone();
+        self.lock().seed(updater).with_on_published(move |updater| {
+            let mut state = shared.lock();
+            state.remember(updater);
+            shared.save(&state);
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn save(&self, state: &State) {
+        if let Some(path) = &self.path
+            && let Err(e) = state.save(path)
+        {
+            tracing::warn!("Failed to save state file: {e:#}");
+        }
+    }
+}
@@ -4,6 +4,7 @@ use worker::{Context, Env, HttpRequest, Result, event};
 
 mod auth;
 mod routes;
+mod transport;
 
 #[event(fetch)]
 async fn fetch(req: HttpRequest, _env: Env, _ctx: Context) -> Result<Response<Body>> {
@@ -1,17 +1,26 @@
 use axum::{Router, http::StatusCode, response::Result, routing::any};
+use worker::console_log;
 
-use rudder_extractors::{Hostname, IpVariant};
+use rudder_core::{
+    Updater,
+    provider::{AttributePolicy, CloudflareProvider, DnsRecordChange, Ownership, RecordOptions},
+    source::IpFamily,
+};
+use rudder_extractors::{Hostname, IpVariant, RecordAttributes, RecordOwner};
+use rudder_http_client::Client;
 
-use crate::auth::EmailAndToken;
+use crate::{auth::EmailAndToken, transport::WorkerTransport};
 
 pub fn router() -> Router {
     Router::new().fallback(any(root))
 }
 
 pub async fn root(
-    _auth: EmailAndToken,
+    auth: EmailAndToken,
     name: Hostname,
     ip: IpVariant,
+    attributes: RecordAttributes,
+    owner: RecordOwner,
 ) -> Result<String, (StatusCode, String)> {
     let ip = match ip {
         IpVariant::Ip(ip) | IpVariant::Auto(ip) => ip,
@@ -23,10 +32,58 @@ pub async fn root(
         }
     };
 
-    // TODO: Implement cloudflare client and verify token + send request to update DNS
+    console_log!("Updating '{name}' to '{ip}' for '{}'", auth.email);
+
+    // 1. Make sure we got a valid API token to use
+    let client = Client::with_transport(WorkerTransport);
+    let cf = client
+        .cloudflare(&auth.token)
+        .map_err(|e| (StatusCode::UNAUTHORIZED, format!("{e:#}")))?;
+    cf.verify_token().await.map_err(|e| {
+        (
+            StatusCode::UNAUTHORIZED,
+            format!("failed to verify given api token: {e:#}"),
+        )
+    })?;
+
+    // 2. Find the zone that the hostname is in, out of the zones the API token is assigned to
+    let provider = CloudflareProvider::discover(cf)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    let zone = provider
+        .zone_for(&name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+
+    console_log!("Found assigned zone '{}' ({})", zone.name, zone.id);
+
+    // 3. Update or create the record, with the attributes that were asked for,
+    //    never changing records that are not owned by rudder unless adopting them
+    let ownership = owner.owner.map_or_else(Ownership::default, Ownership::new);
+    let options = RecordOptions {
+        ttl: attributes.ttl,
+        proxied: attributes.proxied,
+        comment: attributes.comment,
+        tags: attributes.tags,
+        attributes: if attributes.enforce {
+            AttributePolicy::Enforce
+        } else {
+            AttributePolicy::Preserve
+        },
+        ownership: Some(ownership.with_adopt(owner.adopt)),
+        ..RecordOptions::default()
+    };
+    let reconciled = Updater::new(ip, provider, name.to_string())
+        .with_options(options)
+        .with_families([IpFamily::from(ip)])
+        .reconcile()
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{e:#}")))?;
+    let change = reconciled
+        .first()
+        .map_or(DnsRecordChange::Unchanged, |r| r.change);
 
     Ok(format!(
-        "Parsed dynamic DNS request successfully!\
+        "{change}!\
 		\n- Hostname: {name}\
 		\n- IP: {ip}",
     ))
@@ -0,0 +1,48 @@
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use worker::{Fetch, Headers, Method, Request, RequestInit, js_sys::Uint8Array, send::SendFuture};
+
+use rudder_http_client::transport::{Transport, TransportRequest, TransportResponse};
+
+/**
+    A [`Transport`] that sends requests using the `fetch` API of the Workers runtime.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WorkerTransport;
+
+#[async_trait]
+impl Transport for WorkerTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        // NOTE: Workers are single-threaded, and futures from the worker
+        // crate are not Send, so we need to wrap them in a SendFuture here
+        SendFuture::new(fetch(request)).await
+    }
+}
+
+async fn fetch(request: TransportRequest) -> Result<TransportResponse> {
+    let (parts, body) = request.into_parts();
+
+    let mut headers = Headers::new();
+    for (name, value) in &parts.headers {
+        let value = value.to_str().context("invalid header value")?;
+        headers.append(name.as_str(), value)?;
+    }
+
+    let mut init = RequestInit::new();
+    init.with_method(Method::from(parts.method.to_string()))
+        .with_headers(headers);
+    if !body.is_empty() {
+        init.with_body(Some(Uint8Array::from(body.as_slice()).into()));
+    }
+
+    let request = Request::new_with_init(&parts.uri.to_string(), &init)?;
+    let mut response = Fetch::Request(request).send().await?;
+
+    let mut builder = http::Response::builder().status(response.status_code());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+
+    let body = response.bytes().await?;
+    Ok(builder.body(body)?)
+}
@@ -0,0 +1,7 @@
+pub mod provider;
+pub mod retry;
+pub mod source;
+
+mod updater;
+
+pub use self::updater::{Health, IpSourceError, ReconcileError, Reconciled, Updater};
@@ -0,0 +1,212 @@
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+
+use rudder_http_client::{
+    CloudflareClient,
+    models::cloudflare::{
+        CloudflareDnsRecord, CloudflareDnsRecordFilter, CloudflareDnsRecordKind, CloudflareZone,
+    },
+};
+
+use super::{DnsProvider, DnsRecord, DnsRecordKind};
+use crate::retry::FatalError;
+
+/**
+    A [`DnsProvider`] that manages records in one or more Cloudflare zones.
+
+    Records are managed in the zone whose name is the longest suffix of the record
+    name, so that `home.lab.example.com` uses the `lab.example.com` zone over the
+    `example.com` zone, if the API token is assigned to both.
+*/
+#[derive(Debug, Clone)]
+pub struct CloudflareProvider {
+    client: CloudflareClient,
+    zones: Vec<CloudflareZone>,
+}
+
+impl CloudflareProvider {
+    /**
+        Creates a new provider for the given zone.
+    */
+    #[must_use]
+    pub fn new(client: CloudflareClient, zone: CloudflareZone) -> Self {
+        Self {
+            client,
+            zones: vec![zone],
+        }
+    }
+
+    /**
+        Creates a new provider for a zone that is only known by its ID, which
+        is used for all records - for API tokens that are not allowed to read zones.
+    */
+    #[must_use]
+    pub fn with_zone_id(client: CloudflareClient, zone_id: impl Into<String>) -> Self {
+        let zone = CloudflareZone {
+            id: zone_id.into(),
+            ..CloudflareZone::default()
+        };
+        Self::new(client, zone)
+    }
+
+    /**
+        Adds another zone that this provider manages records in.
+    */
+    #[must_use]
+    pub fn with_zone(mut self, zone: CloudflareZone) -> Self {
+        if !self.zones.iter().any(|z| z.id == zone.id) {
+            self.zones.push(zone);
+        }
+        self
+    }
+
+    /**
+        Creates a new provider for all of the zones that the API token
+        of the given client is assigned to.
+
+        # Errors
+
+        Errors if the zones could not be listed, or if the token is not assigned to any zones.
+    */
+    pub async fn discover(client: CloudflareClient) -> Result<Self> {
+        let zones = client
+            .list_zones()
+            .await
+            .context("failed to list zones for given api token")?;
+        if zones.is_empty() {
+            bail!(FatalError::new(
+                "given api token is not assigned to any zones"
+            ));
+        }
+        Ok(Self { client, zones })
+    }
+
+    /**
+        Gets all of the zones that this provider manages records in.
+    */
+    #[must_use]
+    pub fn zones(&self) -> &[CloudflareZone] {
+        &self.zones
+    }
+
+    /**
+        Gets the zone that records with the given name are managed in - the zone
+        with the longest name that the given name is in, or the zone that was
+        given by ID only, if any.
+
+        # Errors
+
+        Errors if the given name is not in any of the zones.
+    */
+    pub fn zone_for(&self, name: &str) -> Result<&CloudflareZone> {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        let zone = self
+            .zones
+            .iter()
+            .filter(|zone| {
+                let zone_name = zone.name.to_ascii_lowercase();
+                zone_name.is_empty()
+                    || name == zone_name
+                    || name.ends_with(&format!(".{zone_name}"))
+            })
+            .max_by_key(|zone| zone.name.len());
+        if let Some(zone) = zone {
+            return Ok(zone);
+        }
+
+        let names = self
+            .zones
+            .iter()
+            .map(|zone| format!("'{}'", zone.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!(FatalError::new(format!(
+            "hostname '{name}' is not in any of the zones that the api token is assigned to ({names})"
+        )))
+    }
+}
+
+#[async_trait]
+impl DnsProvider for CloudflareProvider {
+    async fn find_records(&self, name: &str, kind: DnsRecordKind) -> Result<Vec<DnsRecord>> {
+        let zone = self.zone_for(name)?;
+        let desired_kind = CloudflareDnsRecordKind::from(kind);
+        let filter = CloudflareDnsRecordFilter::new(name, desired_kind);
+        let records = self.client.list_dns_records(&zone.id, &filter).await?;
+        Ok(records
+            .into_iter()
+            .filter(|record| record.name == name && record.kind == desired_kind)
+            .filter_map(|record| DnsRecord::try_from(record).ok())
+            .collect())
+    }
+
+    async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord> {
+        let zone = self.zone_for(&record.name)?;
+        let record = self
+            .client
+            .create_dns_record(&zone.id, record.into())
+            .await?;
+        DnsRecord::try_from(record)
+    }
+
+    async fn update_record(&self, record: DnsRecord) -> Result<DnsRecord> {
+        let zone = self.zone_for(&record.name)?;
+        let record_id = record.id.clone();
+        let record = self
+            .client
+            .update_dns_record(&zone.id, &record_id, record.into())
+            .await?;
+        DnsRecord::try_from(record)
+    }
+
+    async fn delete_record(&self, record: &DnsRecord) -> Result<()> {
+        let zone = self.zone_for(&record.name)?;
+        self.client.delete_dns_record(&zone.id, &record.id).await
+    }
+}
+
+impl From<DnsRecordKind> for CloudflareDnsRecordKind {
+    fn from(kind: DnsRecordKind) -> Self {
+        match kind {
+            DnsRecordKind::A => CloudflareDnsRecordKind::A,
+            DnsRecordKind::AAAA => CloudflareDnsRecordKind::AAAA,
+        }
+    }
+}
+
+impl From<DnsRecord> for CloudflareDnsRecord {
+    fn from(record: DnsRecord) -> Self {
+        let default = CloudflareDnsRecord::default();
+        CloudflareDnsRecord {
+            id: record.id,
+            kind: record.kind.into(),
+            name: record.name,
+            content: record.content,
+            comment: record.comment,
+            tags: record.tags,
+            proxied: record.proxied.unwrap_or(default.proxied),
+            ttl: record.ttl.unwrap_or(default.ttl),
+        }
+    }
+}
+
+impl TryFrom<CloudflareDnsRecord> for DnsRecord {
+    type Error = anyhow::Error;
+    fn try_from(record: CloudflareDnsRecord) -> Result<Self> {
+        let kind = match record.kind {
+            CloudflareDnsRecordKind::A => DnsRecordKind::A,
+            CloudflareDnsRecordKind::AAAA => DnsRecordKind::AAAA,
+            kind => bail!("unsupported dns record kind {kind:?}"),
+        };
+        Ok(DnsRecord {
+            id: record.id,
+            kind,
+            name: record.name,
+            content: record.content,
+            ttl: Some(record.ttl),
+            proxied: Some(record.proxied),
+            comment: record.comment,
+            tags: record.tags,
+        })
+    }
+}
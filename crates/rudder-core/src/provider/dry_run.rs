@@ -0,0 +1,88 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{DnsProvider, DnsRecord, DnsRecordKind};
+
+/**
+    A [`DnsProvider`] that looks up records using another provider, but only
+    logs the changes it would make to them, instead of actually making them.
+
+    Useful for checking configurations and API token permissions safely.
+*/
+#[derive(Debug, Clone)]
+pub struct DryRunProvider<P> {
+    inner: P,
+}
+
+impl<P: DnsProvider> DryRunProvider<P> {
+    /**
+        Creates a new dry run provider, looking up records using the given provider.
+    */
+    #[must_use]
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+
+    async fn find_existing(&self, record: &DnsRecord) -> Result<Option<DnsRecord>> {
+        let records = self.inner.find_records(&record.name, record.kind).await?;
+        Ok(records
+            .into_iter()
+            .find(|existing| existing.id == record.id))
+    }
+}
+
+#[async_trait]
+impl<P: DnsProvider> DnsProvider for DryRunProvider<P> {
+    async fn find_records(&self, name: &str, kind: DnsRecordKind) -> Result<Vec<DnsRecord>> {
+        self.inner.find_records(name, kind).await
+    }
+
+    async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord> {
+        tracing::info!(
+            name = %record.name,
+            kind = %record.kind,
+            old = %"none",
+            new = %record.content,
+            ttl = %or_default(record.ttl),
+            proxied = %or_default(record.proxied),
+            "Dry run, not creating DNS record"
+        );
+        Ok(record)
+    }
+
+    async fn update_record(&self, record: DnsRecord) -> Result<DnsRecord> {
+        // The record only has the new content, so look up the old content to show the difference
+        let existing = self.find_existing(&record).await?;
+        let old = existing.as_ref().map(|existing| existing.content.as_str());
+        tracing::info!(
+            name = %record.name,
+            kind = %record.kind,
+            old = %old.unwrap_or("unknown"),
+            new = %record.content,
+            ttl = %or_default(record.ttl),
+            proxied = %or_default(record.proxied),
+            "Dry run, not updating DNS record"
+        );
+        Ok(record)
+    }
+
+    async fn delete_record(&self, record: &DnsRecord) -> Result<()> {
+        tracing::info!(
+            name = %record.name,
+            kind = %record.kind,
+            old = %record.content,
+            new = %"none",
+            ttl = %or_default(record.ttl),
+            proxied = %or_default(record.proxied),
+            "Dry run, not deleting DNS record"
+        );
+        Ok(())
+    }
+}
+
+/// Formats an optional record attribute, which uses the provider default when missing
+fn or_default<T: Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "default".to_string(), |value| value.to_string())
+}
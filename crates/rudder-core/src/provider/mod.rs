@@ -0,0 +1,487 @@
+#![allow(clippy::missing_errors_doc)]
+
+use std::{
+    fmt::{self, Display},
+    net::IpAddr,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+
+use crate::source::IpFamily;
+
+mod cloudflare;
+mod dry_run;
+mod ownership;
+
+pub use self::cloudflare::CloudflareProvider;
+pub use self::dry_run::DryRunProvider;
+pub use self::ownership::Ownership;
+
+/// The kind of a DNS record managed by a [`DnsProvider`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DnsRecordKind {
+    #[default]
+    A,
+    AAAA,
+}
+
+impl From<IpFamily> for DnsRecordKind {
+    fn from(family: IpFamily) -> Self {
+        match family {
+            IpFamily::V4 => DnsRecordKind::A,
+            IpFamily::V6 => DnsRecordKind::AAAA,
+        }
+    }
+}
+
+impl From<IpAddr> for DnsRecordKind {
+    fn from(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => DnsRecordKind::A,
+            IpAddr::V6(_) => DnsRecordKind::AAAA,
+        }
+    }
+}
+
+impl Display for DnsRecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::A => "A".fmt(f),
+            Self::AAAA => "AAAA".fmt(f),
+        }
+    }
+}
+
+/**
+    A DNS record managed by a [`DnsProvider`].
+
+    Optional attributes that are `None` when creating a record will use
+    the default for the provider, and attributes that are not supported
+    by a provider (such as `proxied` for anything other than Cloudflare)
+    will be ignored by that provider.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsRecord {
+    /// The provider-specific identifier for the record, empty for new records
+    pub id: String,
+    pub kind: DnsRecordKind,
+    pub name: String,
+    pub content: String,
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+    pub comment: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/**
+    What [`DnsProvider::upsert_address`] does when a name has several records of the same kind.
+
+    The policy is applied every time an address is published, so duplicates
+    that are added later are handled as soon as the address changes again.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DuplicatePolicy {
+    /// Updates all of the records to point at the same address
+    #[default]
+    UpdateAll,
+    /// Updates one of the records, and deletes all of the others
+    DeleteExtras,
+    /// Changes none of the records, and fails until all but one of them are removed
+    Refuse,
+}
+
+impl Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UpdateAll => "update-all".fmt(f),
+            Self::DeleteExtras => "delete-extras".fmt(f),
+            Self::Refuse => "refuse".fmt(f),
+        }
+    }
+}
+
+/// What [`DnsProvider::upsert_address`] does with the attributes of existing records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AttributePolicy {
+    /// Keeps the attributes of existing records, such as changes made by hand
+    #[default]
+    Preserve,
+    /// Changes the attributes of existing records to the ones in the [`RecordOptions`]
+    Enforce,
+}
+
+impl Display for AttributePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Preserve => "preserve".fmt(f),
+            Self::Enforce => "enforce".fmt(f),
+        }
+    }
+}
+
+/**
+    Attributes to use for records managed by [`DnsProvider::upsert_address`],
+    and how to handle duplicate existing records, and records owned by others.
+
+    Attributes that are `None` or empty will use the default for the provider
+    for new records, and are never changed on existing records. Other attributes
+    are only changed on existing records with [`AttributePolicy::Enforce`].
+    Without an [`Ownership`], any existing record may be changed.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordOptions {
+    /// The time to live in seconds, where 1 means automatic for Cloudflare
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+    pub comment: Option<String>,
+    pub tags: Vec<String>,
+    pub attributes: AttributePolicy,
+    pub duplicates: DuplicatePolicy,
+    pub ownership: Option<Ownership>,
+}
+
+impl RecordOptions {
+    /**
+        Gets the desired state of an existing record, pointing at the given content,
+        with its attributes enforced or preserved according to the attribute policy.
+    */
+    #[must_use]
+    pub fn desired_record(&self, existing: &DnsRecord, content: &str) -> DnsRecord {
+        let mut record = DnsRecord {
+            content: content.to_string(),
+            ..existing.clone()
+        };
+        if self.attributes == AttributePolicy::Enforce {
+            record.ttl = self.ttl.or(record.ttl);
+            record.proxied = self.proxied.or(record.proxied);
+            if self.comment.is_some() {
+                record.comment.clone_from(&self.comment);
+            }
+            // Tags are a set, so only change them if they are actually different
+            let mut tags = self.tags.clone();
+            let mut existing_tags = existing.tags.clone();
+            tags.sort();
+            existing_tags.sort();
+            if !tags.is_empty() && tags != existing_tags {
+                record.tags.clone_from(&self.tags);
+            }
+        }
+        record.comment = self.owned_comment(record.comment.as_deref());
+        record
+    }
+
+    /**
+        Gets the comment to use for a record with the given comment,
+        marking it as owned if ownership is being enforced.
+    */
+    #[must_use]
+    pub fn owned_comment(&self, comment: Option<&str>) -> Option<String> {
+        match &self.ownership {
+            Some(ownership) => Some(ownership.stamp(comment)),
+            None => comment.map(ToOwned::to_owned),
+        }
+    }
+
+    /**
+        Checks that all of the given existing records may be
+        changed, if ownership is being enforced.
+    */
+    fn claim(&self, records: &[DnsRecord]) -> Result<()> {
+        match &self.ownership {
+            Some(ownership) => ownership.claim(records),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The change made by [`DnsProvider::upsert_address`] or [`DnsProvider::delete_addresses`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsRecordChange {
+    Unchanged,
+    Updated,
+    Created,
+    Deleted,
+}
+
+impl Display for DnsRecordChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unchanged => "No DNS record changes necessary".fmt(f),
+            Self::Updated => "Updated existing DNS record successfully".fmt(f),
+            Self::Created => "Created new DNS record successfully".fmt(f),
+            Self::Deleted => "Deleted existing DNS records successfully".fmt(f),
+        }
+    }
+}
+
+/**
+    A provider that is able to manage DNS records for one or more hostnames.
+
+    Implementors only need to provide the basic record operations, and higher
+    level operations such as [`DnsProvider::upsert_address`] are built on top.
+*/
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    /// Finds all existing records with the given name and kind
+    async fn find_records(&self, name: &str, kind: DnsRecordKind) -> Result<Vec<DnsRecord>>;
+
+    /// Creates a new record, returning the record as created by the provider
+    async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord>;
+
+    /// Updates an existing record, returning the record as updated by the provider
+    async fn update_record(&self, record: DnsRecord) -> Result<DnsRecord>;
+
+    /// Deletes an existing record
+    async fn delete_record(&self, record: &DnsRecord) -> Result<()>;
+
+    /// Finds the first existing record with the given name and kind, if any
+    async fn find_record(&self, name: &str, kind: DnsRecordKind) -> Result<Option<DnsRecord>> {
+        let records = self.find_records(name, kind).await?;
+        Ok(records.into_iter().next())
+    }
+
+    /**
+        Updates the A / AAAA record for the given name to point at the given IP,
+        creating a new record using the given options if one does not already exist.
+
+        If there are several records already, they are reconciled according
+        to the [`DuplicatePolicy`] in the given options.
+
+        Returns the change that was made, along with the record as it is
+        now published, if it is the only record for the name.
+    */
+    async fn upsert_address(
+        &self,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<(DnsRecordChange, Option<DnsRecord>)> {
+        let desired_kind = DnsRecordKind::from(ip);
+        let desired_content = ip.to_string();
+
+        // 1. Look for existing DNS records, to see if we should update instead of creating new,
+        //    making sure that they are ours to change
+        let existing_records = self
+            .find_records(name, desired_kind)
+            .await
+            .context("failed to fetch current dns records")?;
+        options.claim(&existing_records)?;
+
+        // 2. Create a new record if there are none yet
+        if existing_records.is_empty() {
+            tracing::info!(
+                kind = %desired_kind,
+                name = %name,
+                content = %desired_content,
+                "Creating new DNS record"
+            );
+
+            let record = DnsRecord {
+                kind: desired_kind,
+                name: name.to_string(),
+                content: desired_content,
+                ttl: options.ttl,
+                proxied: options.proxied,
+                comment: options.owned_comment(options.comment.as_deref()),
+                tags: options.tags.clone(),
+                ..Default::default()
+            };
+
+            let record = self
+                .create_record(record)
+                .await
+                .context("failed to create dns record")?;
+
+            tracing::info!("Created new DNS record successfully");
+
+            return Ok((DnsRecordChange::Created, Some(record)));
+        }
+
+        // 3. Decide what to do with any duplicate records
+        let (existing_records, extra_records) =
+            split_duplicates(existing_records, &desired_content, options.duplicates)?;
+
+        // 4. Delete the duplicate records that should not be kept
+        let mut change = DnsRecordChange::Unchanged;
+        for extra in &extra_records {
+            tracing::info!(
+                kind = %desired_kind,
+                name = %name,
+                content = %extra.content,
+                "Deleting duplicate DNS record"
+            );
+
+            self.delete_record(extra)
+                .await
+                .context("failed to delete duplicate dns record")?;
+
+            tracing::info!("Deleted duplicate DNS record successfully");
+
+            change = DnsRecordChange::Updated;
+        }
+
+        // 5. Update the remaining records that do not point at the IP yet
+        let mut published = Vec::with_capacity(existing_records.len());
+        for existing in existing_records {
+            let record = options.desired_record(&existing, &desired_content);
+            if record == existing {
+                published.push(existing);
+                continue;
+            }
+
+            tracing::info!(
+                kind = %desired_kind,
+                name = %name,
+                content = %desired_content,
+                "Updating existing DNS record"
+            );
+
+            let record = self
+                .update_record(record)
+                .await
+                .context("failed to update dns record")?;
+
+            tracing::info!("Updated existing DNS record successfully");
+
+            change = DnsRecordChange::Updated;
+            published.push(record);
+        }
+
+        if change == DnsRecordChange::Unchanged {
+            tracing::info!("No DNS record changes necessary");
+        }
+
+        let record = match <[DnsRecord; 1]>::try_from(published) {
+            Ok([record]) => Some(record),
+            Err(_) => None,
+        };
+        Ok((change, record))
+    }
+
+    /**
+        Deletes all records of the given kind for the given name, if any exist,
+        and if they may be changed according to the ownership in the given options.
+    */
+    async fn delete_addresses(
+        &self,
+        name: &str,
+        kind: DnsRecordKind,
+        options: &RecordOptions,
+    ) -> Result<DnsRecordChange> {
+        let existing_records = self
+            .find_records(name, kind)
+            .await
+            .context("failed to fetch current dns records")?;
+        if existing_records.is_empty() {
+            return Ok(DnsRecordChange::Unchanged);
+        }
+        options.claim(&existing_records)?;
+
+        for existing in &existing_records {
+            tracing::info!(
+                kind = %kind,
+                name = %name,
+                content = %existing.content,
+                "Deleting existing DNS record"
+            );
+
+            self.delete_record(existing)
+                .await
+                .context("failed to delete dns record")?;
+        }
+
+        tracing::info!("Deleted existing DNS records successfully");
+
+        Ok(DnsRecordChange::Deleted)
+    }
+}
+
+/**
+    Splits existing records for the same name and kind into the records to keep
+    and update, and the duplicate records to delete, according to the given policy.
+*/
+fn split_duplicates(
+    mut records: Vec<DnsRecord>,
+    desired_content: &str,
+    policy: DuplicatePolicy,
+) -> Result<(Vec<DnsRecord>, Vec<DnsRecord>)> {
+    let (count, Some(first)) = (records.len(), records.first()) else {
+        return Ok((records, Vec::new()));
+    };
+    if count == 1 {
+        return Ok((records, Vec::new()));
+    }
+
+    let kind = first.kind;
+    let name = first.name.clone();
+    tracing::warn!(
+        kind = %kind,
+        name = %name,
+        policy = %policy,
+        "Found {count} DNS records for the same name"
+    );
+
+    match policy {
+        DuplicatePolicy::UpdateAll => Ok((records, Vec::new())),
+        DuplicatePolicy::DeleteExtras => {
+            // Keep a record that is already up to date, if any, to change as little as possible
+            let keep = records
+                .iter()
+                .position(|record| record.content == desired_content)
+                .unwrap_or_default();
+            let kept = records.remove(keep);
+            Ok((vec![kept], records))
+        }
+        DuplicatePolicy::Refuse => {
+            tracing::error!(
+                kind = %kind,
+                name = %name,
+                "Refusing to change duplicate DNS records, remove all but one of them to continue"
+            );
+            bail!("found {count} {kind} records for '{name}', refusing to change them");
+        }
+    }
+}
+
+#[async_trait]
+impl<T: DnsProvider + ?Sized> DnsProvider for Arc<T> {
+    async fn find_records(&self, name: &str, kind: DnsRecordKind) -> Result<Vec<DnsRecord>> {
+        (**self).find_records(name, kind).await
+    }
+
+    async fn create_record(&self, record: DnsRecord) -> Result<DnsRecord> {
+        (**self).create_record(record).await
+    }
+
+    async fn update_record(&self, record: DnsRecord) -> Result<DnsRecord> {
+        (**self).update_record(record).await
+    }
+
+    async fn delete_record(&self, record: &DnsRecord) -> Result<()> {
+        (**self).delete_record(record).await
+    }
+
+    async fn find_record(&self, name: &str, kind: DnsRecordKind) -> Result<Option<DnsRecord>> {
+        (**self).find_record(name, kind).await
+    }
+
+    async fn upsert_address(
+        &self,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<(DnsRecordChange, Option<DnsRecord>)> {
+        (**self).upsert_address(name, ip, options).await
+    }
+
+    async fn delete_addresses(
+        &self,
+        name: &str,
+        kind: DnsRecordKind,
+        options: &RecordOptions,
+    ) -> Result<DnsRecordChange> {
+        (**self).delete_addresses(name, kind, options).await
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Result, bail};
+
+use super::DnsRecord;
+use crate::retry::FatalError;
+
+/// The owner used when none is given, for setups with a single instance of rudder
+const DEFAULT_OWNER: &str = "default";
+
+/**
+    Marks the DNS records that an instance of rudder owns, so that records managed
+    by hand, or by other tools and instances, are never changed by accident.
+
+    The marker is kept in the comment of each record, next to any other comment,
+    since comments can be set on records of every kind, and on every Cloudflare plan.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ownership {
+    owner: String,
+    adopt: bool,
+}
+
+impl Ownership {
+    /**
+        Creates a new ownership marker for the given owner, which should
+        be unique for every instance of rudder that manages the same zone.
+    */
+    #[must_use]
+    pub fn new(owner: impl Into<String>) -> Self {
+        Self {
+            owner: owner.into(),
+            adopt: false,
+        }
+    }
+
+    /**
+        Sets whether to take over existing records that are not owned by this owner,
+        marking them as owned, instead of refusing to change them - false by default.
+    */
+    #[must_use]
+    pub fn with_adopt(mut self, adopt: bool) -> Self {
+        self.adopt = adopt;
+        self
+    }
+
+    /**
+        Gets the marker that is added to the comment of owned records.
+    */
+    #[must_use]
+    pub fn marker(&self) -> String {
+        format!("rudder-owner={}", self.owner)
+    }
+
+    /**
+        Checks if the given record is marked as owned by this owner.
+    */
+    #[must_use]
+    pub fn owns(&self, record: &DnsRecord) -> bool {
+        let marker = self.marker();
+        record
+            .comment
+            .as_deref()
+            .is_some_and(|comment| comment.split_whitespace().any(|word| word == marker))
+    }
+
+    /**
+        Adds the marker to the given comment, unless it is already there.
+    */
+    #[must_use]
+    pub fn stamp(&self, comment: Option<&str>) -> String {
+        let marker = self.marker();
+        match comment.map(str::trim).filter(|comment| !comment.is_empty()) {
+            Some(comment) if comment.split_whitespace().any(|word| word == marker) => {
+                comment.to_string()
+            }
+            Some(comment) => format!("{comment} {marker}"),
+            None => marker,
+        }
+    }
+
+    /**
+        Checks that all of the given existing records may be changed - either because
+        they are owned, or because they are adopted - and fails otherwise.
+    */
+    pub(crate) fn claim(&self, records: &[DnsRecord]) -> Result<()> {
+        let unowned = records
+            .iter()
+            .filter(|record| !self.owns(record))
+            .collect::<Vec<_>>();
+        let Some(first) = unowned.first() else {
+            return Ok(());
+        };
+
+        if self.adopt {
+            for record in &unowned {
+                tracing::info!(
+                    kind = %record.kind,
+                    name = %record.name,
+                    content = %record.content,
+                    owner = %self.owner,
+                    "Adopting DNS record that is not owned by rudder"
+                );
+            }
+            return Ok(());
+        }
+
+        tracing::error!(
+            kind = %first.kind,
+            name = %first.name,
+            owner = %self.owner,
+            "Refusing to change DNS records that are not owned by rudder, adopt them to continue"
+        );
+        // Retrying does not help until the records are adopted or changed by hand
+        bail!(FatalError::new(format!(
+            "found {} {} record(s) for '{}' that are not owned by rudder (owner '{}'), refusing to change them",
+            unowned.len(),
+            first.kind,
+            first.name,
+            self.owner
+        )));
+    }
+}
+
+impl Default for Ownership {
+    fn default() -> Self {
+        Self::new(DEFAULT_OWNER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provider::DnsRecordKind, retry::is_fatal};
+
+    fn record(comment: Option<&str>) -> DnsRecord {
+        DnsRecord {
+            id: String::from("id"),
+            kind: DnsRecordKind::A,
+            name: String::from("home.example.com"),
+            content: String::from("203.0.113.1"),
+            ttl: None,
+            proxied: None,
+            comment: comment.map(String::from),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn claims_owned_and_adopted_records() {
+        let ownership = Ownership::new("home");
+        assert!(
+            ownership
+                .claim(&[record(Some("rudder-owner=home"))])
+                .is_ok()
+        );
+        let adopting = ownership.with_adopt(true);
+        assert!(adopting.claim(&[record(Some("by hand"))]).is_ok());
+    }
+
+    #[test]
+    fn refusing_unowned_records_is_fatal() {
+        let ownership = Ownership::new("home");
+        let error = ownership
+            .claim(&[record(None), record(Some("rudder-owner=other"))])
+            .unwrap_err();
+        assert!(is_fatal(&error));
+    }
+}
@@ -0,0 +1,165 @@
+#![allow(clippy::missing_errors_doc)]
+
+use std::{
+    collections::hash_map::RandomState,
+    error::Error,
+    fmt::{self, Display},
+    hash::BuildHasher,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "tokio")]
+use std::future::Future;
+
+#[cfg(feature = "tokio")]
+use anyhow::Result;
+
+use rudder_http_client::CloudflareApiError;
+
+use crate::ReconcileError;
+
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_mins(5);
+
+/**
+    An error that retrying will never fix, such as an API token
+    that is not assigned to any zones, which should stop the
+    service instead of being retried.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FatalError(String);
+
+impl FatalError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl Display for FatalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for FatalError {}
+
+/**
+    Checks if the given error can never be fixed by retrying - either a
+    [`FatalError`], or an API error caused by invalid credentials or missing
+    zones. Anything else, such as timeouts, is assumed to be transient.
+
+    A [`ReconcileError`] is fatal if the error for any of its address families is.
+*/
+#[must_use]
+pub fn is_fatal(error: &anyhow::Error) -> bool {
+    if let Some(reconcile) = error.downcast_ref::<ReconcileError>() {
+        return reconcile.errors().any(is_fatal);
+    }
+    error.downcast_ref::<FatalError>().is_some()
+        || error
+            .downcast_ref::<CloudflareApiError>()
+            .is_some_and(CloudflareApiError::is_permanent)
+}
+
+/**
+    Exponential backoff with jitter, for spacing out retries of failed
+    operations - the delay doubles after every consecutive failure,
+    up to a maximum, and is randomized to avoid retrying in lockstep.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    /**
+        Creates a new backoff, starting at `initial` and doubling up to `max`.
+    */
+    #[must_use]
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            failures: 0,
+        }
+    }
+
+    /**
+        Gets the number of consecutive failures since the last reset.
+    */
+    #[must_use]
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /**
+        Records a failure, and gets how long to wait before retrying.
+
+        The delay is somewhere between half of, and the full,
+        exponentially increasing delay for this many failures.
+    */
+    pub fn next_delay(&mut self) -> Duration {
+        let exponent = self.failures.min(16);
+        self.failures = self.failures.saturating_add(1);
+
+        let delay = self.initial.saturating_mul(1 << exponent).min(self.max);
+        let half = delay / 2;
+        half + half.mul_f64(jitter())
+    }
+
+    /**
+        Resets the delay back to the initial one, after a success.
+    */
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(DEFAULT_INITIAL_DELAY, DEFAULT_MAX_DELAY)
+    }
+}
+
+/**
+    Runs the given operation until it succeeds, waiting between attempts
+    using the given backoff, and logging every failure as a warning.
+
+    Returns immediately if the operation fails with a [fatal](is_fatal) error.
+*/
+#[cfg(feature = "tokio")]
+pub async fn retry<T, F, Fut>(what: &str, mut backoff: Backoff, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(e) => {
+                let delay = backoff.next_delay();
+                tracing::warn!(
+                    failures = backoff.failures(),
+                    "Failed to {what}, retrying in {:.1}s: {e:#}",
+                    delay.as_secs_f64()
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Gets a random number between 0 and 1, which only needs to be good enough for jitter
+fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let hash = RandomState::new().hash_one(nanos);
+    #[allow(clippy::cast_precision_loss)]
+    let value = (hash >> 11) as f64 / (1u64 << 53) as f64;
+    value
+}
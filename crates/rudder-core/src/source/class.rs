@@ -0,0 +1,136 @@
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/**
+    The kind of network an IP address belongs to, which
+    decides whether it is reachable from the public internet.
+
+    Gateways regularly report addresses that are not, such as when the
+    ISP uses carrier-grade NAT, or when the router is itself behind
+    another router, and those addresses should never be published.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressClass {
+    /// A globally routable address
+    Public,
+    /// A private address (RFC 1918), or a unique local IPv6 address
+    Private,
+    /// A shared address (RFC 6598), used by ISPs for carrier-grade NAT
+    Cgnat,
+    /// A loopback address, only reachable from this device
+    Loopback,
+    /// A link-local address, only reachable from the same network link
+    LinkLocal,
+    /// An address reserved for use in documentation and examples
+    Documentation,
+    /// Any other address that should never appear on the public internet
+    Bogon,
+}
+
+impl AddressClass {
+    /**
+        Classifies the given IP address.
+    */
+    #[must_use]
+    pub fn of(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => Self::of_v4(ip),
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => Self::of_v4(ip),
+                None => Self::of_v6(ip),
+            },
+        }
+    }
+
+    fn of_v4(ip: Ipv4Addr) -> Self {
+        let [a, b, c, _] = ip.octets();
+        if ip.is_private() {
+            Self::Private
+        } else if a == 100 && (64..128).contains(&b) {
+            Self::Cgnat
+        } else if ip.is_loopback() {
+            Self::Loopback
+        } else if ip.is_link_local() {
+            Self::LinkLocal
+        } else if ip.is_documentation() {
+            Self::Documentation
+        } else if a == 0
+            || (a == 192 && b == 0 && c == 0)
+            || (a == 198 && (18..20).contains(&b))
+            || a >= 224
+        {
+            // This network, protocol assignments, benchmarking,
+            // multicast, reserved, and the broadcast address
+            Self::Bogon
+        } else {
+            Self::Public
+        }
+    }
+
+    fn of_v6(ip: Ipv6Addr) -> Self {
+        let segments = ip.segments();
+        if ip.is_loopback() {
+            Self::Loopback
+        } else if ip.is_unique_local() {
+            Self::Private
+        } else if ip.is_unicast_link_local() {
+            Self::LinkLocal
+        } else if (segments[0] == 0x2001 && segments[1] == 0x0db8)
+            || (segments[0] == 0x3fff && (segments[1] & 0xf000) == 0)
+        {
+            // 2001:db8::/32 and 3fff::/20
+            Self::Documentation
+        } else if (segments[0] & 0xe000) != 0x2000 {
+            // Only 2000::/3 is currently allocated for global unicast
+            Self::Bogon
+        } else {
+            Self::Public
+        }
+    }
+
+    /**
+        Checks if addresses of this class are reachable from the public internet.
+    */
+    #[must_use]
+    pub fn is_routable(self) -> bool {
+        self == Self::Public
+    }
+}
+
+impl Display for AddressClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Public => "public".fmt(f),
+            Self::Private => "private".fmt(f),
+            Self::Cgnat => "carrier-grade NAT".fmt(f),
+            Self::Loopback => "loopback".fmt(f),
+            Self::LinkLocal => "link-local".fmt(f),
+            Self::Documentation => "documentation".fmt(f),
+            Self::Bogon => "bogon".fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(ip: &str) -> AddressClass {
+        AddressClass::of(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn classifies_v6_documentation() {
+        assert_eq!(class("2001:db8::1"), AddressClass::Documentation);
+        assert_eq!(class("3fff::1"), AddressClass::Documentation);
+        assert_eq!(class("3fff:fff::1"), AddressClass::Documentation);
+    }
+
+    #[test]
+    fn classifies_rest_of_3ff0_as_public() {
+        assert_eq!(class("3ff0::1"), AddressClass::Public);
+        assert_eq!(class("3fff:1000::1"), AddressClass::Public);
+    }
+}
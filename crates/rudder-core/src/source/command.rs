@@ -0,0 +1,105 @@
+use std::{net::IpAddr, process::Stdio};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::{IpFamily, IpSource};
+
+/**
+    An [`IpSource`] that runs a user-supplied command, and
+    reads IP addresses from its output - one per line.
+
+    The address family being requested is passed to the command in
+    the `RUDDER_IP_FAMILY` environment variable, as either `ipv4` or
+    `ipv6`, but the command may also print addresses for both families.
+*/
+#[derive(Debug, Clone)]
+pub struct CommandSource {
+    display: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSource {
+    /**
+        Creates a new source that runs the given program with the given arguments.
+    */
+    pub fn new(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let program = program.into();
+        Self {
+            display: program.clone(),
+            program,
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /**
+        Creates a new source that runs the given command using the system shell.
+    */
+    pub fn shell(command: impl Into<String>) -> Self {
+        let command = command.into();
+        let source = if cfg!(windows) {
+            Self::new("cmd", ["/C", &command])
+        } else {
+            Self::new("sh", ["-c", &command])
+        };
+        Self {
+            display: command,
+            ..source
+        }
+    }
+}
+
+#[async_trait]
+impl IpSource for CommandSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        let family_name = match family {
+            IpFamily::V4 => "ipv4",
+            IpFamily::V6 => "ipv6",
+        };
+
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .env("RUDDER_IP_FAMILY", family_name)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .with_context(|| format!("failed to run command '{}'", self.display))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.trim().is_empty() {
+                bail!("command '{}' failed with {}", self.display, output.status);
+            }
+            bail!(
+                "command '{}' failed with {}: {}",
+                self.display,
+                output.status,
+                stderr.trim()
+            );
+        }
+
+        let stdout = String::from_utf8(output.stdout)
+            .with_context(|| format!("command '{}' printed invalid utf8", self.display))?;
+
+        let mut found = None;
+        for line in stdout.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let ip = line.parse::<IpAddr>().with_context(|| {
+                format!(
+                    "command '{}' printed an invalid ip address: '{line}'",
+                    self.display
+                )
+            })?;
+            if found.is_none() && family.matches(ip) {
+                found = Some(ip);
+            }
+        }
+
+        Ok(found)
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    future::{Future, poll_fn},
+    net::IpAddr,
+    pin::Pin,
+    sync::{Mutex, MutexGuard, PoisonError},
+    task::Poll,
+};
+
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+
+use super::{IpFamily, IpSource};
+
+/// How a [`ConsensusSource`] decides on an address from the answers of its sources
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ConsensusPolicy {
+    /// Uses the address from the first source, in order, that found one
+    #[default]
+    FirstSuccess,
+    /// Uses the address that more than half of the sources that found one agree on
+    Majority,
+    /// Uses the address only if every source succeeded, and all sources that found one agree on it
+    AllMustAgree,
+}
+
+impl Display for ConsensusPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FirstSuccess => "first-success".fmt(f),
+            Self::Majority => "majority".fmt(f),
+            Self::AllMustAgree => "all-must-agree".fmt(f),
+        }
+    }
+}
+
+/**
+    An [`IpSource`] that queries several other sources at the same time,
+    and applies a [`ConsensusPolicy`] to their answers.
+
+    A warning is logged whenever the sources disagree on the address,
+    which usually means that this device is behind more than one NAT,
+    such as with CGNAT, and some of the sources see an address that
+    is not reachable from the public internet.
+
+    Sources that have no address for a family abstain, and do not count
+    towards the policy. An error is only returned when every source fails,
+    or when the policy can not be satisfied.
+*/
+#[derive(Default)]
+pub struct ConsensusSource {
+    policy: ConsensusPolicy,
+    sources: Vec<(String, Box<dyn IpSource>)>,
+    /// The indices of the sources that found the last address for each family
+    agreed: Mutex<HashMap<IpFamily, Vec<usize>>>,
+}
+
+impl ConsensusSource {
+    #[must_use]
+    pub fn new(policy: ConsensusPolicy) -> Self {
+        Self {
+            policy,
+            sources: Vec::new(),
+            agreed: Mutex::default(),
+        }
+    }
+
+    /**
+        Adds a source to query, with a name used to identify it in logs and errors.
+    */
+    #[must_use]
+    pub fn with_source(mut self, name: impl Into<String>, source: impl IpSource + 'static) -> Self {
+        self.sources.push((name.into(), Box::new(source)));
+        self
+    }
+
+    fn agreed(&self) -> MutexGuard<'_, HashMap<IpFamily, Vec<usize>>> {
+        self.agreed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn decide(
+        &self,
+        family: IpFamily,
+        answers: Vec<Result<Option<IpAddr>>>,
+    ) -> Result<Option<IpAddr>> {
+        // 1. Split the answers into addresses, in source order, and failures
+        let mut found = Vec::new();
+        let mut failed = Vec::new();
+        for ((name, _), answer) in self.sources.iter().zip(answers) {
+            match answer {
+                Ok(Some(ip)) => found.push((name.as_str(), ip)),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(source = %name, "Failed to get {family} address: {e:#}");
+                    failed.push((name.as_str(), e));
+                }
+            }
+        }
+
+        // 2. Count how many sources found each address, and warn when they disagree
+        let mut counts: Vec<(IpAddr, usize)> = Vec::new();
+        for (_, ip) in &found {
+            match counts.iter_mut().find(|(other, _)| other == ip) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((*ip, 1)),
+            }
+        }
+        if counts.len() > 1 {
+            tracing::warn!(
+                policy = %self.policy,
+                "IP sources disagree on the {family} address, this device may be behind \
+                 more than one NAT: {}",
+                describe(&found)
+            );
+        }
+
+        // 3. Apply the policy, only failing completely when nothing was found
+        if found.is_empty() {
+            return match failed.into_iter().next_back() {
+                Some((_, e)) if self.sources.len() == 1 => Err(e),
+                Some((name, e)) => {
+                    Err(e.context(format!("every IP source failed, the last one was '{name}'")))
+                }
+                None => Ok(None),
+            };
+        }
+        match self.policy {
+            ConsensusPolicy::FirstSuccess => Ok(found.first().map(|(_, ip)| *ip)),
+            ConsensusPolicy::Majority => {
+                match counts.iter().find(|(_, count)| count * 2 > found.len()) {
+                    Some((ip, _)) => Ok(Some(*ip)),
+                    None => bail!(
+                        "no majority of IP sources agree on the {family} address: {}",
+                        describe(&found)
+                    ),
+                }
+            }
+            ConsensusPolicy::AllMustAgree => {
+                if let Some((name, e)) = failed.into_iter().next() {
+                    return Err(e.context(format!(
+                        "IP source '{name}' failed, so sources can not all agree"
+                    )));
+                }
+                match counts.as_slice() {
+                    [(ip, _)] => Ok(Some(*ip)),
+                    _ => Err(anyhow!(
+                        "IP sources do not all agree on the {family} address: {}",
+                        describe(&found)
+                    )),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ConsensusSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsensusSource")
+            .field("policy", &self.policy)
+            .field(
+                "sources",
+                &self
+                    .sources
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl IpSource for ConsensusSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        let answers = join_all(
+            self.sources
+                .iter()
+                .map(|(_, source)| source.get_ip(family))
+                .collect(),
+        )
+        .await;
+
+        // Remember which sources found the decided address, to describe its origin
+        let found = answers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, answer)| match answer {
+                Ok(Some(ip)) => Some((index, *ip)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let decided = self.decide(family, answers);
+        let agreed = match &decided {
+            Ok(Some(ip)) => found
+                .into_iter()
+                .filter(|(_, found)| found == ip)
+                .map(|(index, _)| index)
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.agreed().insert(family, agreed);
+
+        decided
+    }
+
+    async fn changed(&self) -> Result<()> {
+        let mut changes = self
+            .sources
+            .iter()
+            .map(|(_, source)| source.changed())
+            .collect::<Vec<_>>();
+        poll_fn(|cx| {
+            for change in &mut changes {
+                if let Poll::Ready(result) = change.as_mut().poll(cx) {
+                    return Poll::Ready(result);
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn watches_changes(&self) -> bool {
+        self.sources
+            .iter()
+            .all(|(_, source)| source.watches_changes())
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        let agreed = self.agreed().get(&family).cloned().unwrap_or_default();
+        if agreed.is_empty() {
+            return None;
+        }
+        let mut origins = Vec::new();
+        for index in agreed {
+            let (name, source) = &self.sources[index];
+            origins.push(source.origin(family).await.unwrap_or_else(|| name.clone()));
+        }
+        Some(origins.join(", "))
+    }
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Polls all the futures at the same time, returning their outputs in order
+async fn join_all<T>(futures: Vec<BoxFuture<'_, T>>) -> Vec<T> {
+    let mut pending = futures.into_iter().map(Some).collect::<Vec<_>>();
+    let mut outputs = pending.iter().map(|_| None).collect::<Vec<_>>();
+    poll_fn(|cx| {
+        for (future, output) in pending.iter_mut().zip(&mut outputs) {
+            if let Some(inner) = future
+                && let Poll::Ready(value) = inner.as_mut().poll(cx)
+            {
+                *output = Some(value);
+                *future = None;
+            }
+        }
+        if pending.iter().all(Option::is_none) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
+
+fn describe(found: &[(&str, IpAddr)]) -> String {
+    found
+        .iter()
+        .map(|(name, ip)| format!("'{name}' found {ip}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,529 @@
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use tokio::net::lookup_host;
+
+use super::{
+    IpFamily, IpSource,
+    udp::{self, random_bytes},
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+
+const HEADER_LEN: usize = 12;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+const TYPE_A: u16 = 1;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+
+const CLASS_IN: u16 = 1;
+const CLASS_CH: u16 = 3;
+
+/// A public DNS service that answers queries with the address they came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DnsService {
+    /// An `A` / `AAAA` query for `myip.opendns.com` against the OpenDNS resolvers
+    OpenDns,
+    /// A `CH TXT` query for `whoami.cloudflare` against the Cloudflare resolvers
+    Cloudflare,
+    /// A `TXT` query for `o-o.myaddr.l.google.com` against the Google nameservers
+    Google,
+}
+
+impl DnsService {
+    /// All services, in the order they are tried by default
+    pub const ALL: [DnsService; 3] = [
+        DnsService::OpenDns,
+        DnsService::Cloudflare,
+        DnsService::Google,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::OpenDns => "myip.opendns.com",
+            Self::Cloudflare => "whoami.cloudflare",
+            Self::Google => "o-o.myaddr.l.google.com",
+        }
+    }
+
+    fn record_type(self, family: IpFamily) -> u16 {
+        match (self, family) {
+            (Self::OpenDns, IpFamily::V4) => TYPE_A,
+            (Self::OpenDns, IpFamily::V6) => TYPE_AAAA,
+            (Self::Cloudflare | Self::Google, _) => TYPE_TXT,
+        }
+    }
+
+    fn class(self) -> u16 {
+        match self {
+            Self::Cloudflare => CLASS_CH,
+            Self::OpenDns | Self::Google => CLASS_IN,
+        }
+    }
+
+    /// Gets the resolvers that answer queries for this service, as `host:port` pairs
+    fn default_resolvers(self) -> &'static [&'static str] {
+        match self {
+            Self::OpenDns => &["208.67.222.222:53", "[2620:119:35::35]:53"],
+            Self::Cloudflare => &["1.1.1.1:53", "[2606:4700:4700::1111]:53"],
+            Self::Google => &["216.239.32.10:53", "[2001:4860:4802:32::a]:53"],
+        }
+    }
+}
+
+impl Display for DnsService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpenDns => "OpenDNS".fmt(f),
+            Self::Cloudflare => "Cloudflare".fmt(f),
+            Self::Google => "Google".fmt(f),
+        }
+    }
+}
+
+/**
+    An [`IpSource`] that sends DNS queries to public DNS services,
+    which answer with the address they saw the query coming from -
+    the external address of this device.
+
+    This is cheaper and less rate-limited than HTTP echo services.
+    Services are tried in order until one of them answers, and queries
+    are sent over UDP from a socket of the requested address family,
+    so this works for both IPv4 and IPv6.
+
+    Each service is queried using its own public resolvers by default,
+    but the resolvers can be changed, for example to a local DNS server.
+*/
+#[derive(Debug, Clone)]
+pub struct DnsSource {
+    services: Vec<DnsService>,
+    resolvers: Option<Vec<String>>,
+    timeout: Duration,
+}
+
+impl DnsSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            services: DnsService::ALL.to_vec(),
+            resolvers: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /**
+        Sets which services to query, in order.
+    */
+    #[must_use]
+    pub fn with_services(mut self, services: impl IntoIterator<Item = DnsService>) -> Self {
+        self.services = services.into_iter().collect();
+        self.services.dedup();
+        self
+    }
+
+    /**
+        Sets the resolvers to send queries to, as `host:port` pairs,
+        instead of the public resolvers for each service.
+    */
+    #[must_use]
+    pub fn with_resolvers(
+        mut self,
+        resolvers: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.resolvers = Some(resolvers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /**
+        Sets how long to wait for each resolver to answer before timing out.
+    */
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn query(
+        &self,
+        service: DnsService,
+        resolver: &str,
+        family: IpFamily,
+    ) -> Result<Option<IpAddr>> {
+        // 1. Resolve the resolver, using only addresses in the requested family
+        let Some(addr) = lookup_host(resolver)
+            .await
+            .with_context(|| format!("failed to resolve dns resolver '{resolver}'"))?
+            .find(|addr| family.matches(addr.ip()))
+        else {
+            return Ok(None);
+        };
+
+        // 2. Connect a socket in the same family, no connectivity means no address
+        let Some(socket) = udp::connect(addr).await? else {
+            return Ok(None);
+        };
+
+        // 3. Send the query, retransmitting until we get a matching answer
+        let id = u16::from_be_bytes(random_bytes::<2>());
+        let record_type = service.record_type(family);
+        let request = encode_query(id, service.name(), record_type, service.class());
+        let ip = udp::exchange(&socket, &request, INITIAL_RTO, self.timeout, |packet| {
+            decode_answer(packet, id, record_type, family)
+        })
+        .await
+        .with_context(|| format!("failed to query {service} dns resolver '{resolver}'"))?;
+
+        Ok(Some(ip))
+    }
+}
+
+impl Default for DnsSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl IpSource for DnsSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        let mut last_error = None;
+        for &service in &self.services {
+            let resolvers = match &self.resolvers {
+                Some(resolvers) => resolvers.iter().map(String::as_str).collect(),
+                None => service.default_resolvers().to_vec(),
+            };
+            for resolver in resolvers {
+                match self.query(service, resolver, family).await {
+                    Ok(Some(ip)) => return Ok(Some(ip)),
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::debug!(%service, %resolver, "Failed to query dns resolver: {e:#}");
+                        last_error.replace(e);
+                    }
+                }
+            }
+        }
+        match last_error {
+            Some(e) => Err(e.context("no dns resolver answered")),
+            None => Ok(None),
+        }
+    }
+}
+
+fn encode_query(id: u16, name: &str, record_type: u16, class: u16) -> Vec<u8> {
+    let mut query = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    // One question, and no answer, authority, or additional records
+    query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        query.push(u8::try_from(label.len()).expect("labels are short"));
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&class.to_be_bytes());
+    query
+}
+
+/**
+    Decodes a DNS response, returning the first address in its answers.
+
+    Returns `None` for packets that are not a response to our query,
+    which should be ignored, and an error for error responses.
+*/
+fn decode_answer(
+    packet: &[u8],
+    id: u16,
+    record_type: u16,
+    family: IpFamily,
+) -> Result<Option<IpAddr>> {
+    if packet.len() < HEADER_LEN || packet[0..2] != id.to_be_bytes() {
+        return Ok(None);
+    }
+
+    let flags = u16::from_be_bytes([packet[2], packet[3]]);
+    if flags & FLAG_RESPONSE == 0 {
+        return Ok(None);
+    }
+    if flags & FLAG_TRUNCATED != 0 {
+        bail!("dns response is truncated");
+    }
+    match flags & 0x000F {
+        0 => {}
+        3 => bail!("dns resolver responded with NXDOMAIN"),
+        5 => bail!("dns resolver refused the query"),
+        rcode => bail!("dns resolver responded with error code {rcode}"),
+    }
+
+    let questions = u16::from_be_bytes([packet[4], packet[5]]);
+    let answers = u16::from_be_bytes([packet[6], packet[7]]);
+
+    // Skip over the questions, which are echoed back from our query
+    let mut offset = HEADER_LEN;
+    for _ in 0..questions {
+        offset = skip_name(packet, offset).context("dns response is malformed")? + 4;
+    }
+
+    for _ in 0..answers {
+        offset = skip_name(packet, offset).context("dns response is malformed")?;
+        let Some(fixed) = packet.get(offset..offset + 10) else {
+            bail!("dns response is malformed");
+        };
+        let kind = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let len = usize::from(u16::from_be_bytes([fixed[8], fixed[9]]));
+        offset += 10;
+        let Some(data) = packet.get(offset..offset + len) else {
+            bail!("dns response is malformed");
+        };
+        offset += len;
+
+        if kind != record_type {
+            continue;
+        }
+        if let Some(ip) = decode_record(kind, data).filter(|ip| family.matches(*ip)) {
+            return Ok(Some(ip));
+        }
+    }
+
+    bail!("dns response did not contain an {family} address")
+}
+
+fn decode_record(kind: u16, data: &[u8]) -> Option<IpAddr> {
+    match kind {
+        TYPE_A => {
+            let bytes: [u8; 4] = data.try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(bytes)))
+        }
+        TYPE_AAAA => {
+            let bytes: [u8; 16] = data.try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(bytes)))
+        }
+        TYPE_TXT => {
+            // TXT data is a sequence of length-prefixed strings, and only
+            // some of them may be addresses, such as with Google's service
+            let mut rest = data;
+            while let Some((&len, tail)) = rest.split_first() {
+                let text = tail.get(..usize::from(len))?;
+                if let Some(ip) = std::str::from_utf8(text)
+                    .ok()
+                    .and_then(|text| text.trim().parse().ok())
+                {
+                    return Some(ip);
+                }
+                rest = &tail[usize::from(len)..];
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Skips over a possibly compressed name, returning the offset after it
+fn skip_name(packet: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(offset)?;
+        match len {
+            0 => return Some(offset + 1),
+            // A pointer to a name elsewhere always ends the name
+            len if len & 0xC0 == 0xC0 => return Some(offset + 2),
+            len => offset += 1 + usize::from(len),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::net::UdpSocket;
+
+    use super::*;
+
+    const RCODE_NXDOMAIN: u16 = 3;
+
+    /**
+        Encodes a response to the given query, echoing its question back, with an
+        answer for each of the given records - in the class of the question, and
+        pointing at the question name.
+    */
+    fn encode_response(query: &[u8], rcode: u16, records: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let class = question_class(query);
+        let mut response = query[0..2].to_vec();
+        response.extend_from_slice(&(FLAG_RESPONSE | FLAG_RECURSION_DESIRED | rcode).to_be_bytes());
+        response.extend_from_slice(&[0, 1]);
+        response.extend_from_slice(&u16::try_from(records.len()).unwrap().to_be_bytes());
+        response.extend_from_slice(&[0, 0, 0, 0]);
+        response.extend_from_slice(&query[HEADER_LEN..]);
+        for (kind, data) in records {
+            response.extend_from_slice(&[0xC0, 0x0C]);
+            response.extend_from_slice(&kind.to_be_bytes());
+            response.extend_from_slice(&class.to_be_bytes());
+            response.extend_from_slice(&[0, 0, 0, 0]);
+            response.extend_from_slice(&u16::try_from(data.len()).unwrap().to_be_bytes());
+            response.extend_from_slice(data);
+        }
+        response
+    }
+
+    /// Gets the record type of the question in the given query
+    fn question_type(query: &[u8]) -> u16 {
+        let offset = skip_name(query, HEADER_LEN).unwrap();
+        u16::from_be_bytes([query[offset], query[offset + 1]])
+    }
+
+    /// Gets the class of the question in the given query
+    fn question_class(query: &[u8]) -> u16 {
+        let offset = skip_name(query, HEADER_LEN).unwrap();
+        u16::from_be_bytes([query[offset + 2], query[offset + 3]])
+    }
+
+    /// Encodes TXT record data, with each of the given strings prefixed by its length
+    fn txt(strings: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for string in strings {
+            data.push(u8::try_from(string.len()).unwrap());
+            data.extend_from_slice(string.as_bytes());
+        }
+        data
+    }
+
+    /**
+        Binds a local DNS server, which answers every
+        query with the response returned for it.
+    */
+    async fn server(bind: &str, respond: impl Fn(&[u8]) -> Vec<u8> + Send + 'static) -> SocketAddr {
+        let socket = UdpSocket::bind(bind).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                socket.send_to(&respond(&buf[..len]), peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    fn source(service: DnsService, addr: SocketAddr) -> DnsSource {
+        DnsSource::new()
+            .with_services([service])
+            .with_resolvers([addr.to_string()])
+            .with_timeout(Duration::from_millis(300))
+    }
+
+    #[tokio::test]
+    async fn answers_a_query() {
+        let addr = server("127.0.0.1:0", |query| {
+            assert_eq!(question_type(query), TYPE_A);
+            encode_response(query, 0, &[(TYPE_A, vec![203, 0, 113, 7])])
+        })
+        .await;
+        let ip = source(DnsService::OpenDns, addr)
+            .get_ip(IpFamily::V4)
+            .await
+            .unwrap();
+        assert_eq!(ip, Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7))));
+    }
+
+    #[tokio::test]
+    async fn answers_aaaa_query() {
+        let expected = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x42);
+        let addr = server("[::1]:0", move |query| {
+            assert_eq!(question_type(query), TYPE_AAAA);
+            encode_response(query, 0, &[(TYPE_AAAA, expected.octets().to_vec())])
+        })
+        .await;
+        let ip = source(DnsService::OpenDns, addr)
+            .get_ip(IpFamily::V6)
+            .await
+            .unwrap();
+        assert_eq!(ip, Some(IpAddr::V6(expected)));
+    }
+
+    #[tokio::test]
+    async fn fails_on_empty_answer() {
+        let addr = server("127.0.0.1:0", |query| encode_response(query, 0, &[])).await;
+        let error = source(DnsService::OpenDns, addr)
+            .get_ip(IpFamily::V4)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("did not contain an IPv4 address"),
+            "{error:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_on_nxdomain() {
+        let addr = server("127.0.0.1:0", |query| {
+            encode_response(query, RCODE_NXDOMAIN, &[])
+        })
+        .await;
+        let error = source(DnsService::OpenDns, addr)
+            .get_ip(IpFamily::V4)
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("NXDOMAIN"), "{error:#}");
+    }
+
+    #[tokio::test]
+    async fn answers_cloudflare_txt_query() {
+        let addr = server("127.0.0.1:0", |query| {
+            assert_eq!(question_type(query), TYPE_TXT);
+            assert_eq!(question_class(query), CLASS_CH);
+            encode_response(query, 0, &[(TYPE_TXT, txt(&["203.0.113.7"]))])
+        })
+        .await;
+        let ip = source(DnsService::Cloudflare, addr)
+            .get_ip(IpFamily::V4)
+            .await
+            .unwrap();
+        assert_eq!(ip, Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7))));
+    }
+
+    #[tokio::test]
+    async fn answers_google_txt_query_with_several_strings() {
+        let addr = server("127.0.0.1:0", |query| {
+            assert_eq!(question_type(query), TYPE_TXT);
+            assert_eq!(question_class(query), CLASS_IN);
+            let data = txt(&["edns0-client-subnet 198.51.100.0/24", "203.0.113.7"]);
+            encode_response(query, 0, &[(TYPE_TXT, data)])
+        })
+        .await;
+        let ip = source(DnsService::Google, addr)
+            .get_ip(IpFamily::V4)
+            .await
+            .unwrap();
+        assert_eq!(ip, Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7))));
+    }
+
+    #[tokio::test]
+    async fn fails_on_truncated_txt_string() {
+        let addr = server("127.0.0.1:0", |query| {
+            // The string claims to be longer than the rest of the record
+            let mut data = txt(&["203.0.113.7"]);
+            data[0] = 20;
+            encode_response(query, 0, &[(TYPE_TXT, data)])
+        })
+        .await;
+        let error = source(DnsService::Cloudflare, addr)
+            .get_ip(IpFamily::V4)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("did not contain an IPv4 address"),
+            "{error:#}"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
+
+use super::IpFamily;
+
+#[cfg(feature = "upnp")]
+pub use self::fallback::{GatewayProtocol, GatewaySource};
+
+/**
+    Finds the address of the default gateway / router for the given
+    address family, with the given port, or `None` if there is none.
+*/
+pub(crate) fn default_gateway(family: IpFamily, port: u16) -> Option<SocketAddr> {
+    // No default interface means that there is no default route either
+    let interface = netdev::get_default_interface().ok()?;
+    let gateway = interface.gateway?;
+
+    let ip = match family {
+        IpFamily::V4 => gateway.ipv4.first().copied().map(IpAddr::V4),
+        IpFamily::V6 => gateway.ipv6.first().copied().map(IpAddr::V6),
+    };
+
+    // IPv6 gateways are usually link-local, and link-local
+    // addresses can only be reached through a specific interface
+    ip.map(|ip| match ip {
+        IpAddr::V6(ip) if ip.is_unicast_link_local() => {
+            SocketAddr::V6(SocketAddrV6::new(ip, port, 0, interface.index))
+        }
+        ip => SocketAddr::new(ip, port),
+    })
+}
+
+#[cfg(feature = "upnp")]
+mod fallback {
+    use std::{
+        collections::HashMap,
+        fmt,
+        net::IpAddr,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+        time::Duration,
+    };
+
+    use anyhow::Result;
+    use async_trait::async_trait;
+
+    use super::super::{IpFamily, IpSource, NatPmpSource, PcpSource, UpnpSource};
+
+    /// A protocol for asking a gateway / router for its external address
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum GatewayProtocol {
+        /// uPnP Internet Gateway Device
+        Igd,
+        /// Port Control Protocol
+        Pcp,
+        /// NAT Port Mapping Protocol
+        NatPmp,
+    }
+
+    impl GatewayProtocol {
+        pub const ALL: [GatewayProtocol; 3] = [
+            GatewayProtocol::Igd,
+            GatewayProtocol::Pcp,
+            GatewayProtocol::NatPmp,
+        ];
+    }
+
+    impl fmt::Display for GatewayProtocol {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Igd => write!(f, "uPnP IGD"),
+                Self::Pcp => write!(f, "PCP"),
+                Self::NatPmp => write!(f, "NAT-PMP"),
+            }
+        }
+    }
+
+    /**
+        An [`IpSource`] that asks the gateway / router for its external
+        address, trying each of the given protocols in order until one
+        of them succeeds - uPnP IGD, PCP, and then NAT-PMP by default.
+
+        Routers commonly support only one or two of these protocols,
+        so this source works with a lot more of them than any single one.
+
+        Changes are detected through uPnP eventing, when IGD is one of the protocols,
+        and no other protocol had to be used instead.
+    */
+    #[derive(Debug, Clone)]
+    pub struct GatewaySource {
+        protocols: Vec<GatewayProtocol>,
+        gateway: Option<IpAddr>,
+        timeout: Option<Duration>,
+        igd: UpnpSource,
+        answered: Arc<Mutex<HashMap<IpFamily, GatewayProtocol>>>,
+    }
+
+    impl GatewaySource {
+        #[must_use]
+        pub fn new() -> Self {
+            Self {
+                protocols: GatewayProtocol::ALL.to_vec(),
+                gateway: None,
+                timeout: None,
+                igd: UpnpSource::new(),
+                answered: Arc::default(),
+            }
+        }
+
+        /**
+            Sets the protocols to try, in order.
+        */
+        #[must_use]
+        pub fn with_protocols(
+            mut self,
+            protocols: impl IntoIterator<Item = GatewayProtocol>,
+        ) -> Self {
+            self.protocols = protocols.into_iter().collect();
+            self
+        }
+
+        /**
+            Sets the address of the gateway / router to ask for PCP and NAT-PMP,
+            instead of the default gateway. uPnP IGD always finds the gateway itself.
+        */
+        #[must_use]
+        pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+            self.gateway = Some(gateway);
+            self
+        }
+
+        /**
+            Sets how long to wait for each protocol before timing out.
+        */
+        #[must_use]
+        pub fn with_timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = Some(timeout);
+            self.igd = UpnpSource::new().with_timeout(timeout);
+            self
+        }
+
+        fn pcp(&self) -> PcpSource {
+            let mut source = PcpSource::new();
+            if let Some(gateway) = self.gateway {
+                source = source.with_gateway(gateway);
+            }
+            if let Some(timeout) = self.timeout {
+                source = source.with_timeout(timeout);
+            }
+            source
+        }
+
+        fn natpmp(&self) -> NatPmpSource {
+            let mut source = NatPmpSource::new();
+            if let Some(gateway) = self.gateway {
+                source = source.with_gateway(gateway);
+            }
+            if let Some(timeout) = self.timeout {
+                source = source.with_timeout(timeout);
+            }
+            source
+        }
+
+        async fn get_ip_with(
+            &self,
+            protocol: GatewayProtocol,
+            family: IpFamily,
+        ) -> Result<Option<IpAddr>> {
+            match protocol {
+                GatewayProtocol::Igd => self.igd.get_ip(family).await,
+                GatewayProtocol::Pcp => self.pcp().get_ip(family).await,
+                GatewayProtocol::NatPmp => self.natpmp().get_ip(family).await,
+            }
+        }
+
+        fn answered(&self) -> MutexGuard<'_, HashMap<IpFamily, GatewayProtocol>> {
+            self.answered.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        /// Remembers which protocol answered last for the given family, if any
+        fn set_answered(&self, family: IpFamily, protocol: Option<GatewayProtocol>) {
+            let mut answered = self.answered();
+            match protocol {
+                Some(protocol) => answered.insert(family, protocol),
+                None => answered.remove(&family),
+            };
+        }
+    }
+
+    impl Default for GatewaySource {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    #[async_trait]
+    impl IpSource for GatewaySource {
+        async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+            let mut last_error = None;
+            for &protocol in &self.protocols {
+                match self.get_ip_with(protocol, family).await {
+                    Ok(Some(ip)) => {
+                        tracing::debug!(%protocol, %ip, "Got external address from gateway");
+                        self.set_answered(family, Some(protocol));
+                        return Ok(Some(ip));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::debug!(%protocol, "Failed to get external address: {e:#}");
+                        last_error.replace(e);
+                    }
+                }
+            }
+            self.set_answered(family, None);
+            match last_error {
+                Some(e) => Err(e.context("no gateway / router protocol succeeded")),
+                None => Ok(None),
+            }
+        }
+
+        async fn changed(&self) -> Result<()> {
+            if self.watches_changes() {
+                self.igd.changed().await
+            } else {
+                std::future::pending().await
+            }
+        }
+
+        fn watches_changes(&self) -> bool {
+            // Gateways that do not answer through IGD are unlikely to send IGD events,
+            // so changes are only detected once IGD answered for every family
+            let answered = self.answered();
+            self.protocols.contains(&GatewayProtocol::Igd)
+                && answered
+                    .values()
+                    .all(|&protocol| protocol == GatewayProtocol::Igd)
+        }
+
+        async fn origin(&self, family: IpFamily) -> Option<String> {
+            let protocol = self.answered().get(&family).copied()?;
+            match protocol {
+                GatewayProtocol::Igd => self.igd.origin(family).await,
+                GatewayProtocol::Pcp => self.pcp().origin(family).await,
+                GatewayProtocol::NatPmp => self.natpmp().origin(family).await,
+            }
+        }
+    }
+}
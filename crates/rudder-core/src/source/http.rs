@@ -0,0 +1,62 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use rudder_http_client::{Client, IpEchoClient};
+
+use super::{IpFamily, IpSource};
+
+/// The default echo service used for IPv4 addresses
+pub const DEFAULT_HTTP_URL_V4: &str = "https://ipv4.icanhazip.com";
+
+/// The default echo service used for IPv6 addresses
+pub const DEFAULT_HTTP_URL_V6: &str = "https://ipv6.icanhazip.com";
+
+/**
+    An [`IpSource`] that asks a "what is my IP" HTTP echo service,
+    which responds with the address of the caller in plain text.
+
+    By default, separate IPv4-only and IPv6-only services are used
+    for each address family, so that both can be found. When using
+    a custom URL, only addresses in the family that the service
+    responded with will be returned.
+*/
+#[derive(Debug, Clone)]
+pub struct HttpSource {
+    client: IpEchoClient,
+    url: Option<String>,
+}
+
+impl HttpSource {
+    #[must_use]
+    pub fn new(client: &Client) -> Self {
+        Self {
+            client: client.ip_echo(),
+            url: None,
+        }
+    }
+
+    /**
+        Sets a custom URL for the echo service to use.
+    */
+    #[must_use]
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+}
+
+#[async_trait]
+impl IpSource for HttpSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        let url = self.url.as_deref().unwrap_or(match family {
+            IpFamily::V4 => DEFAULT_HTTP_URL_V4,
+            IpFamily::V6 => DEFAULT_HTTP_URL_V6,
+        });
+
+        let ip = self.client.get_ip(url).await?;
+
+        Ok(family.matches(ip).then_some(ip))
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+
+use super::{InterfaceAddress, InterfaceScope};
+
+/**
+    Lists the addresses of all interfaces that are up, using the
+    portable `getifaddrs` / `GetAdaptersAddresses` APIs.
+
+    Scopes are derived from the addresses themselves, and
+    temporary or deprecated addresses can not be detected.
+*/
+pub(super) async fn list_addresses() -> Result<Vec<InterfaceAddress>> {
+    let interfaces = if_addrs::get_if_addrs().context("failed to list network interfaces")?;
+    Ok(interfaces
+        .into_iter()
+        .filter(if_addrs::Interface::is_oper_up)
+        .map(|iface| {
+            let ip = iface.ip();
+            let scope = if iface.is_loopback() {
+                InterfaceScope::Host
+            } else if iface.is_link_local() {
+                InterfaceScope::Link
+            } else {
+                InterfaceScope::Global
+            };
+            InterfaceAddress {
+                interface: iface.name,
+                ip,
+                scope,
+                temporary: false,
+                deprecated: false,
+            }
+        })
+        .collect())
+}
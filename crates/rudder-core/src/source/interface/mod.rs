@@ -0,0 +1,146 @@
+use std::{fmt, net::IpAddr};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{IpFamily, IpSource, is_usable, network::NetworkWatcher};
+
+#[cfg(not(target_os = "linux"))]
+mod if_addrs;
+#[cfg(target_os = "linux")]
+mod netlink;
+
+#[cfg(not(target_os = "linux"))]
+use self::if_addrs::list_addresses;
+#[cfg(target_os = "linux")]
+use self::netlink::list_addresses;
+
+/// The scope of an address assigned to a network interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InterfaceScope {
+    /// Globally routable addresses
+    #[default]
+    Global,
+    /// Addresses only valid within a site
+    Site,
+    /// Addresses only valid on the link, such as link-local addresses
+    Link,
+    /// Addresses only valid on this device, such as loopback addresses
+    Host,
+}
+
+impl fmt::Display for InterfaceScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Site => write!(f, "site"),
+            Self::Link => write!(f, "link"),
+            Self::Host => write!(f, "host"),
+        }
+    }
+}
+
+/// An address assigned to a local network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddress {
+    /// The name of the interface, such as `eth0`
+    pub interface: String,
+    pub ip: IpAddr,
+    pub scope: InterfaceScope,
+    /// If this is a temporary (privacy extensions) IPv6 address
+    pub temporary: bool,
+    /// If this address is deprecated, or not yet usable
+    pub deprecated: bool,
+}
+
+/**
+    An [`IpSource`] that uses the addresses assigned to
+    the local network interfaces of this device.
+
+    This only works as an external address when the device is not
+    behind NAT - such as when it holds a public IPv4 address directly,
+    or a global IPv6 address. Loopback, link-local, and unique local
+    addresses are never returned, and neither are temporary or
+    deprecated IPv6 addresses, since those change too often.
+
+    On Linux, addresses are listed through rtnetlink, and changes are
+    detected through netlink notifications - other platforms can not tell
+    temporary or deprecated addresses apart, and only support polling.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceSource {
+    name: Option<String>,
+    scope: InterfaceScope,
+    watcher: NetworkWatcher,
+}
+
+impl InterfaceSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Sets the name of the interface to use addresses from,
+        such as `eth0` - otherwise, all interfaces are used.
+    */
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /**
+        Sets the scope that addresses must have - global by default.
+    */
+    #[must_use]
+    pub fn with_scope(mut self, scope: InterfaceScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /**
+        Lists all addresses assigned to the interfaces of this
+        source, including the ones that would never be used.
+    */
+    pub async fn addresses(&self) -> Result<Vec<InterfaceAddress>> {
+        let mut addresses = list_addresses().await?;
+        addresses.retain(|addr| {
+            self.name
+                .as_ref()
+                .is_none_or(|name| &addr.interface == name)
+        });
+        Ok(addresses)
+    }
+
+    /**
+        Checks if the given address could be used by this source.
+    */
+    #[must_use]
+    pub fn is_candidate(&self, address: &InterfaceAddress) -> bool {
+        address.scope == self.scope
+            && !address.temporary
+            && !address.deprecated
+            && is_usable(address.ip)
+    }
+}
+
+#[async_trait]
+impl IpSource for InterfaceSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        Ok(self
+            .addresses()
+            .await?
+            .into_iter()
+            .find(|addr| family.matches(addr.ip) && self.is_candidate(addr))
+            .map(|addr| addr.ip))
+    }
+
+    async fn changed(&self) -> Result<()> {
+        self.watcher.changed().await
+    }
+
+    fn watches_changes(&self) -> bool {
+        NetworkWatcher::SUPPORTED
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use anyhow::{Context, Result, bail};
+use futures_util::{
+    TryStreamExt,
+    future::{Either, select},
+};
+use rtnetlink::{
+    Handle,
+    packet_route::{
+        address::{AddressAttribute, AddressFlags, AddressScope},
+        link::{LinkAttribute, State},
+    },
+};
+
+use super::{InterfaceAddress, InterfaceScope};
+
+/**
+    Lists the addresses of all interfaces that are not down, using rtnetlink.
+*/
+pub(super) async fn list_addresses() -> Result<Vec<InterfaceAddress>> {
+    let (connection, handle, _) =
+        rtnetlink::new_connection().context("failed to open netlink socket")?;
+
+    // The connection must be driven while we send requests through the handle
+    let query = Box::pin(query_addresses(handle));
+    match select(query, connection).await {
+        Either::Left((result, _)) => result,
+        Either::Right(((), _)) => bail!("netlink connection closed unexpectedly"),
+    }
+}
+
+async fn query_addresses(handle: Handle) -> Result<Vec<InterfaceAddress>> {
+    // 1. Find the names of all interfaces that are not down
+    let mut names = HashMap::new();
+    let mut links = handle.link().get().execute();
+    while let Some(link) = links.try_next().await.context("failed to list links")? {
+        let mut name = None;
+        let mut down = false;
+        for attribute in link.attributes {
+            match attribute {
+                LinkAttribute::IfName(n) => name = Some(n),
+                LinkAttribute::OperState(State::Down) => down = true,
+                _ => {}
+            }
+        }
+        if let Some(name) = name
+            && !down
+        {
+            names.insert(link.header.index, name);
+        }
+    }
+
+    // 2. List all of the addresses on those interfaces
+    let mut addresses = Vec::new();
+    let mut messages = handle.address().get().execute();
+    while let Some(message) = messages
+        .try_next()
+        .await
+        .context("failed to list addresses")?
+    {
+        let Some(name) = names.get(&message.header.index) else {
+            continue;
+        };
+
+        // The full set of flags is only in the attribute, for
+        // IPv4 point-to-point links the local address is the
+        // address of this device, and not the peer address
+        let mut flags = AddressFlags::from_bits_retain(u32::from(message.header.flags.bits()));
+        let mut address = None;
+        let mut local = None;
+        for attribute in message.attributes {
+            match attribute {
+                AddressAttribute::Address(ip) => address = Some(ip),
+                AddressAttribute::Local(ip) => local = Some(ip),
+                AddressAttribute::Flags(f) => flags = f,
+                _ => {}
+            }
+        }
+        let Some(ip) = local.or(address) else {
+            continue;
+        };
+
+        // Temporary IPv6 addresses share their flag with secondary IPv4 addresses
+        let temporary = matches!(ip, IpAddr::V6(_)) && flags.contains(AddressFlags::Secondary);
+        let deprecated = flags.intersects(
+            AddressFlags::Deprecated | AddressFlags::Tentative | AddressFlags::Dadfailed,
+        );
+
+        addresses.push(InterfaceAddress {
+            interface: name.clone(),
+            ip,
+            scope: match message.header.scope {
+                AddressScope::Universe => InterfaceScope::Global,
+                AddressScope::Site => InterfaceScope::Site,
+                AddressScope::Link => InterfaceScope::Link,
+                _ => InterfaceScope::Host,
+            },
+            temporary,
+            deprecated,
+        });
+    }
+
+    Ok(addresses)
+}
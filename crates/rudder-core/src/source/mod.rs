@@ -0,0 +1,252 @@
+#![allow(clippy::missing_errors_doc)]
+
+use std::{
+    fmt::{self, Display},
+    io::ErrorKind,
+    net::IpAddr,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+mod class;
+mod consensus;
+mod http;
+mod network;
+mod routable;
+mod route;
+mod split;
+
+#[cfg(feature = "command")]
+mod command;
+#[cfg(feature = "dns")]
+mod dns;
+#[cfg(feature = "natpmp")]
+mod gateway;
+#[cfg(feature = "interface")]
+mod interface;
+#[cfg(feature = "natpmp")]
+mod natpmp;
+#[cfg(all(target_os = "linux", feature = "netlink"))]
+mod netlink;
+#[cfg(feature = "natpmp")]
+mod pcp;
+#[cfg(feature = "stun")]
+mod stun;
+#[cfg(feature = "upnp")]
+mod upnp;
+
+#[cfg(any(feature = "dns", feature = "stun", feature = "natpmp"))]
+mod udp;
+
+pub use self::class::AddressClass;
+pub use self::consensus::{ConsensusPolicy, ConsensusSource};
+pub use self::http::{DEFAULT_HTTP_URL_V4, DEFAULT_HTTP_URL_V6, HttpSource};
+pub use self::routable::RoutableSource;
+pub use self::route::RouteSource;
+pub use self::split::SplitSource;
+
+#[cfg(feature = "command")]
+pub use self::command::CommandSource;
+#[cfg(feature = "dns")]
+pub use self::dns::{DnsService, DnsSource};
+#[cfg(all(feature = "natpmp", feature = "upnp"))]
+pub use self::gateway::{GatewayProtocol, GatewaySource};
+#[cfg(feature = "interface")]
+pub use self::interface::{InterfaceAddress, InterfaceScope, InterfaceSource};
+#[cfg(feature = "natpmp")]
+pub use self::natpmp::NatPmpSource;
+#[cfg(feature = "natpmp")]
+pub use self::pcp::PcpSource;
+#[cfg(feature = "stun")]
+pub use self::stun::{DEFAULT_STUN_SERVERS, StunSource};
+#[cfg(feature = "upnp")]
+pub use self::upnp::UpnpSource;
+
+/// An IP address family - either IPv4 or IPv6
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    /// Both address families, in order
+    pub const ALL: [IpFamily; 2] = [IpFamily::V4, IpFamily::V6];
+
+    /// Checks if the given IP address belongs to this family
+    #[must_use]
+    pub fn matches(self, ip: IpAddr) -> bool {
+        IpFamily::from(ip) == self
+    }
+}
+
+impl From<IpAddr> for IpFamily {
+    fn from(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => IpFamily::V4,
+            IpAddr::V6(_) => IpFamily::V6,
+        }
+    }
+}
+
+impl Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V4 => "IPv4".fmt(f),
+            Self::V6 => "IPv6".fmt(f),
+        }
+    }
+}
+
+/**
+    A source for the current external IP address of this device.
+
+    Sources return `None` when they have no address for a family, for
+    example when a source only supports IPv4, or when there currently
+    is no IPv6 connectivity, and only return errors on actual failures.
+
+    An [`IpAddr`] is also a source by itself, which always
+    returns the same address, and is useful when the address
+    is already known - such as when handling a DDNS request.
+
+    An [`Option`] of a source is also a source, which never
+    returns any address when it is `None`.
+
+    # Change detection
+
+    Sources may also be able to detect when their address changes, such
+    as through netlink notifications or uPnP eventing, which makes it
+    possible to react to changes within seconds instead of polling.
+    Such sources return `true` from [`IpSource::watches_changes`],
+    and implement [`IpSource::changed`].
+*/
+#[async_trait]
+pub trait IpSource: Send + Sync {
+    /// Gets the current external IP address for the given family, if any
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>>;
+
+    /**
+        Waits until the address from this source may have changed.
+
+        Changes may be reported even if the address is still the same,
+        and an error means that changes can no longer be detected.
+
+        Sources that do not detect changes never return from this.
+    */
+    async fn changed(&self) -> Result<()> {
+        std::future::pending().await
+    }
+
+    /**
+        Checks if this source detects all changes to its address through
+        [`IpSource::changed`], meaning that polling is only a fallback.
+
+        This may change once the source has found an address, such as
+        when it depends on which gateway / router protocol answered.
+    */
+    fn watches_changes(&self) -> bool {
+        false
+    }
+
+    /**
+        Describes where the last address for the given family came from, such
+        as the protocol and address of the gateway / router that answered.
+
+        Sources return `None` when there is nothing more to say than which
+        kind of source they are, which is what callers should show instead.
+    */
+    async fn origin(&self, _family: IpFamily) -> Option<String> {
+        None
+    }
+}
+
+/**
+    Checks if the given IP address could be used as an external address,
+    meaning it is not unspecified, loopback, link-local, or unique local.
+*/
+pub(crate) fn is_usable(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => !ip.is_unspecified() && !ip.is_loopback() && !ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            !ip.is_unspecified()
+                && !ip.is_loopback()
+                && !ip.is_unicast_link_local()
+                && !ip.is_unique_local()
+        }
+    }
+}
+
+/**
+    Checks if the given IO error kind means that there is no
+    connectivity, such as when an address family is not available.
+*/
+pub(crate) fn is_unavailable(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::AddrNotAvailable
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::HostUnreachable
+            | ErrorKind::Unsupported
+    )
+}
+
+#[async_trait]
+impl IpSource for IpAddr {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        Ok(family.matches(*self).then_some(*self))
+    }
+
+    fn watches_changes(&self) -> bool {
+        // A fixed address never changes
+        true
+    }
+}
+
+#[async_trait]
+impl<T: IpSource + ?Sized> IpSource for Box<T> {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        (**self).get_ip(family).await
+    }
+
+    async fn changed(&self) -> Result<()> {
+        (**self).changed().await
+    }
+
+    fn watches_changes(&self) -> bool {
+        (**self).watches_changes()
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        (**self).origin(family).await
+    }
+}
+
+#[async_trait]
+impl<T: IpSource> IpSource for Option<T> {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        match self {
+            Some(source) => source.get_ip(family).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn changed(&self) -> Result<()> {
+        match self {
+            Some(source) => source.changed().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn watches_changes(&self) -> bool {
+        // Having no source means there is nothing to poll for
+        self.as_ref().is_none_or(IpSource::watches_changes)
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        match self {
+            Some(source) => source.origin(family).await,
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+
+use super::{IpFamily, IpSource, gateway::default_gateway, udp};
+
+/// The port that NAT-PMP and PCP servers listen on
+pub(crate) const GATEWAY_PORT: u16 = 5351;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+const INITIAL_RTO: Duration = Duration::from_millis(250);
+
+const VERSION: u8 = 0;
+const OP_EXTERNAL_ADDRESS: u8 = 0;
+const OP_RESPONSE: u8 = 128;
+
+/**
+    An [`IpSource`] that asks the gateway / router for its external
+    address using NAT-PMP ([RFC 6886]), which is supported by many
+    routers that do not support the full uPnP IGD protocol.
+
+    NAT-PMP only supports IPv4, so this source never returns
+    any IPv6 addresses. The gateway is the default gateway
+    of this device, unless a different address is given.
+
+    [RFC 6886]: https://datatracker.ietf.org/doc/html/rfc6886
+*/
+#[derive(Debug, Clone)]
+pub struct NatPmpSource {
+    gateway: Option<IpAddr>,
+    timeout: Duration,
+}
+
+impl NatPmpSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            gateway: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /**
+        Sets the address of the gateway / router to ask, instead of the default gateway.
+    */
+    #[must_use]
+    pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /**
+        Sets how long to wait for the gateway / router to respond before timing out.
+    */
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Gets the gateway to ask, if any
+    fn gateway(&self) -> Result<Option<SocketAddr>> {
+        Ok(match self.gateway {
+            Some(ip @ IpAddr::V4(_)) => Some(SocketAddr::new(ip, GATEWAY_PORT)),
+            Some(IpAddr::V6(_)) => bail!("NAT-PMP gateway address must be an IPv4 address"),
+            None => default_gateway(IpFamily::V4, GATEWAY_PORT),
+        })
+    }
+}
+
+impl Default for NatPmpSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl IpSource for NatPmpSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        if family != IpFamily::V4 {
+            return Ok(None);
+        }
+
+        // 1. Find the gateway to ask, NAT-PMP is only spoken over IPv4
+        let Some(gateway) = self.gateway()? else {
+            return Ok(None);
+        };
+        tracing::debug!(%gateway, "Asking gateway / router for external address through NAT-PMP");
+
+        let Some(socket) = udp::connect(gateway).await? else {
+            return Ok(None);
+        };
+
+        // 2. Ask the gateway for its external address
+        let request = [VERSION, OP_EXTERNAL_ADDRESS];
+        let ip = udp::exchange(
+            &socket,
+            &request,
+            INITIAL_RTO,
+            self.timeout,
+            decode_response,
+        )
+        .await
+        .context("failed to get external ip through NAT-PMP")?;
+
+        Ok(Some(IpAddr::V4(ip)))
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        if family != IpFamily::V4 {
+            return None;
+        }
+        let gateway = self.gateway().ok()??;
+        Some(format!("natpmp ({})", gateway.ip()))
+    }
+}
+
+fn decode_response(packet: &[u8]) -> Result<Option<Ipv4Addr>> {
+    if packet.len() < 4 || packet[0] != VERSION || packet[1] != OP_RESPONSE | OP_EXTERNAL_ADDRESS {
+        return Ok(None);
+    }
+
+    let result = u16::from_be_bytes([packet[2], packet[3]]);
+    if result != 0 {
+        bail!(
+            "gateway responded with an error: {}",
+            result_message(result)
+        );
+    }
+
+    let Some(bytes) = packet.get(8..12) else {
+        bail!("NAT-PMP response is truncated");
+    };
+    Ok(Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])))
+}
+
+fn result_message(result: u16) -> String {
+    match result {
+        1 => String::from("unsupported version"),
+        2 => String::from("not authorized / refused"),
+        3 => String::from("network failure"),
+        4 => String::from("out of resources"),
+        5 => String::from("unsupported opcode"),
+        _ => format!("unknown result code {result}"),
+    }
+}
@@ -0,0 +1,76 @@
+use std::{fmt, pin::Pin, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use futures_channel::mpsc::UnboundedReceiver;
+use futures_util::{
+    StreamExt,
+    future::{Either, select},
+};
+use rtnetlink::{
+    MulticastGroup, packet_core::NetlinkMessage, packet_route::RouteNetlinkMessage,
+    proto::Connection, sys::SocketAddr,
+};
+use tokio::time::timeout;
+
+/// How long to wait for more notifications, after receiving one
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+/// The notifications that may mean that an external address has changed
+const GROUPS: &[MulticastGroup] = &[
+    MulticastGroup::Link,
+    MulticastGroup::Ipv4Ifaddr,
+    MulticastGroup::Ipv4Route,
+    MulticastGroup::Ipv6Ifaddr,
+    MulticastGroup::Ipv6Route,
+];
+
+type Messages = UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>;
+
+/**
+    A subscription to rtnetlink notifications for
+    changes to links, addresses, and routes.
+*/
+pub(crate) struct NetlinkSubscription {
+    connection: Pin<Box<Connection<RouteNetlinkMessage>>>,
+    messages: Messages,
+}
+
+impl NetlinkSubscription {
+    pub(crate) fn new() -> Result<Self> {
+        let (connection, _, messages) = rtnetlink::new_multicast_connection(GROUPS)
+            .context("failed to subscribe to netlink notifications")?;
+        Ok(Self {
+            connection: Box::pin(connection),
+            messages,
+        })
+    }
+
+    /**
+        Waits for a notification, and then for notifications to stop
+        arriving, so that a burst of them is only reported once.
+    */
+    pub(crate) async fn changed(&mut self) -> Result<()> {
+        self.next().await?;
+        while let Ok(result) = timeout(QUIET_PERIOD, self.next()).await {
+            result?;
+        }
+        Ok(())
+    }
+
+    async fn next(&mut self) -> Result<()> {
+        // The connection must be driven for any messages to arrive
+        match select(self.connection.as_mut(), self.messages.next()).await {
+            Either::Left(((), _)) | Either::Right((None, _)) => {
+                bail!("netlink connection closed unexpectedly")
+            }
+            Either::Right((Some(_), _)) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for NetlinkSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetlinkSubscription")
+            .finish_non_exhaustive()
+    }
+}
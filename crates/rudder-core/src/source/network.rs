@@ -0,0 +1,48 @@
+use anyhow::Result;
+
+#[cfg(all(target_os = "linux", feature = "netlink"))]
+use std::sync::Arc;
+
+#[cfg(all(target_os = "linux", feature = "netlink"))]
+use tokio::sync::Mutex;
+
+#[cfg(all(target_os = "linux", feature = "netlink"))]
+use super::netlink::NetlinkSubscription;
+
+/**
+    Watches for changes to the addresses and routes of the local
+    network interfaces, for sources that get their address from them.
+
+    On Linux this lazily subscribes to netlink notifications the first
+    time it is used, other platforms do not support watching for changes.
+*/
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NetworkWatcher {
+    #[cfg(all(target_os = "linux", feature = "netlink"))]
+    subscription: Arc<Mutex<Option<NetlinkSubscription>>>,
+}
+
+impl NetworkWatcher {
+    /// If watching for changes is supported on this platform
+    pub(crate) const SUPPORTED: bool = cfg!(all(target_os = "linux", feature = "netlink"));
+
+    #[cfg(all(target_os = "linux", feature = "netlink"))]
+    pub(crate) async fn changed(&self) -> Result<()> {
+        let mut guard = self.subscription.lock().await;
+        let subscription = match guard.as_mut() {
+            Some(subscription) => subscription,
+            None => guard.insert(NetlinkSubscription::new()?),
+        };
+        let result = subscription.changed().await;
+        if result.is_err() {
+            guard.take();
+        }
+        result
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "netlink")))]
+    #[allow(clippy::unused_async)]
+    pub(crate) async fn changed(&self) -> Result<()> {
+        std::future::pending().await
+    }
+}
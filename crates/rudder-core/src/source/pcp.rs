@@ -0,0 +1,207 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+
+use super::{
+    IpFamily, IpSource,
+    gateway::default_gateway,
+    natpmp::GATEWAY_PORT,
+    udp::{self, random_bytes},
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+const INITIAL_RTO: Duration = Duration::from_secs(3);
+const DELETE_TIMEOUT: Duration = Duration::from_secs(1);
+
+const VERSION: u8 = 2;
+const OP_MAP: u8 = 1;
+const OP_RESPONSE: u8 = 0x80;
+const PROTOCOL_UDP: u8 = 17;
+const MAP_LIFETIME: u32 = 30;
+
+const REQUEST_LEN: usize = 60;
+
+/**
+    An [`IpSource`] that asks the gateway / router for its external
+    address using PCP ([RFC 6887]), the successor to NAT-PMP.
+
+    PCP has no request for only getting the external address, so this
+    creates a short-lived mapping for an unused port, reads the address
+    assigned to it, and then deletes the mapping again. The gateway is
+    the default gateway of this device, unless a different one is given.
+
+    [RFC 6887]: https://datatracker.ietf.org/doc/html/rfc6887
+*/
+#[derive(Debug, Clone)]
+pub struct PcpSource {
+    gateway: Option<IpAddr>,
+    timeout: Duration,
+}
+
+impl PcpSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            gateway: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /**
+        Sets the address of the gateway / router to ask, instead of the default gateway.
+    */
+    #[must_use]
+    pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /**
+        Sets how long to wait for the gateway / router to respond before timing out.
+    */
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Gets the gateway to ask for an address of the given family, if any
+    fn gateway_for(&self, family: IpFamily) -> Option<SocketAddr> {
+        match self.gateway {
+            Some(ip) if family.matches(ip) => Some(SocketAddr::new(ip, GATEWAY_PORT)),
+            Some(_) => None,
+            None => default_gateway(family, GATEWAY_PORT),
+        }
+    }
+}
+
+impl Default for PcpSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl IpSource for PcpSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        // 1. Find the gateway to ask, in the same family as the wanted address
+        let Some(gateway) = self.gateway_for(family) else {
+            return Ok(None);
+        };
+        tracing::debug!(%gateway, "Asking gateway / router for external address through PCP");
+
+        let Some(socket) = udp::connect(gateway).await? else {
+            return Ok(None);
+        };
+        let local = socket
+            .local_addr()
+            .context("failed to get local address for PCP")?;
+
+        // 2. Create a mapping for the port of our socket, which
+        //    nothing is listening on, and read the assigned address
+        let nonce = random_bytes::<12>();
+        let request = encode_map_request(local, &nonce, MAP_LIFETIME);
+        let ip = udp::exchange(&socket, &request, INITIAL_RTO, self.timeout, |packet| {
+            decode_map_response(packet, &nonce)
+        })
+        .await
+        .context("failed to get external ip through PCP")?;
+
+        // 3. Delete the mapping again, it expires on its own soon if this fails
+        let request = encode_map_request(local, &nonce, 0);
+        let deleted = udp::exchange(
+            &socket,
+            &request,
+            DELETE_TIMEOUT,
+            DELETE_TIMEOUT,
+            |packet| decode_map_response(packet, &nonce),
+        )
+        .await;
+        if let Err(e) = deleted {
+            tracing::debug!("Failed to delete PCP mapping: {e:#}");
+        }
+
+        Ok(family.matches(ip).then_some(ip))
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        let gateway = self.gateway_for(family)?;
+        Some(format!("pcp ({})", gateway.ip()))
+    }
+}
+
+fn encode_map_request(local: SocketAddr, nonce: &[u8; 12], lifetime: u32) -> [u8; REQUEST_LEN] {
+    // Both client and external addresses are always 16 bytes,
+    // with IPv4 addresses being sent as IPv4-mapped IPv6 addresses
+    let (client, suggested) = match local.ip() {
+        IpAddr::V4(ip) => (ip.to_ipv6_mapped(), Ipv4Addr::UNSPECIFIED.to_ipv6_mapped()),
+        IpAddr::V6(ip) => (ip, Ipv6Addr::UNSPECIFIED),
+    };
+
+    let mut request = [0u8; REQUEST_LEN];
+    request[0] = VERSION;
+    request[1] = OP_MAP;
+    request[4..8].copy_from_slice(&lifetime.to_be_bytes());
+    request[8..24].copy_from_slice(&client.octets());
+    request[24..36].copy_from_slice(nonce);
+    request[36] = PROTOCOL_UDP;
+    request[40..42].copy_from_slice(&local.port().to_be_bytes());
+    request[44..60].copy_from_slice(&suggested.octets());
+    request
+}
+
+fn decode_map_response(packet: &[u8], nonce: &[u8; 12]) -> Result<Option<IpAddr>> {
+    // Servers that only speak NAT-PMP respond with their own version
+    if packet.len() >= 4 && packet[0] == 0 && packet[1] & OP_RESPONSE != 0 {
+        bail!("gateway does not support PCP, only NAT-PMP");
+    }
+    if packet.len() < 4 || packet[0] != VERSION || packet[1] != OP_RESPONSE | OP_MAP {
+        return Ok(None);
+    }
+
+    let result = packet[3];
+    if result != 0 {
+        bail!(
+            "gateway responded with an error: {}",
+            result_message(result)
+        );
+    }
+
+    if packet.len() < REQUEST_LEN {
+        bail!("PCP response is truncated");
+    }
+    if packet[24..36] != *nonce {
+        return Ok(None);
+    }
+
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&packet[44..60]);
+    let ip = Ipv6Addr::from(octets);
+    Ok(Some(match ip.to_ipv4_mapped() {
+        Some(ip) => IpAddr::V4(ip),
+        None => IpAddr::V6(ip),
+    }))
+}
+
+fn result_message(result: u8) -> String {
+    match result {
+        1 => String::from("unsupported version"),
+        2 => String::from("not authorized"),
+        3 => String::from("malformed request"),
+        4 => String::from("unsupported opcode"),
+        5 => String::from("unsupported option"),
+        6 => String::from("malformed option"),
+        7 => String::from("network failure"),
+        8 => String::from("no resources"),
+        9 => String::from("unsupported protocol"),
+        10 => String::from("user exceeded quota"),
+        11 => String::from("cannot provide external address"),
+        12 => String::from("address mismatch"),
+        13 => String::from("excessive remote peers"),
+        _ => format!("unknown result code {result}"),
+    }
+}
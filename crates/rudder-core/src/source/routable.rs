@@ -0,0 +1,113 @@
+use std::{fmt, future::poll_fn, net::IpAddr, pin::pin, task::Poll};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+
+use super::{AddressClass, IpFamily, IpSource};
+
+/**
+    An [`IpSource`] that refuses to return addresses that are not
+    reachable from the public internet, such as private and
+    carrier-grade NAT addresses reported by a gateway.
+
+    When the inner source returns such an address, the fallback source
+    is asked instead, if there is one - for example an HTTP or STUN
+    source, which sees the actual external address past any NAT.
+    Otherwise, an error is returned, so that nothing gets published.
+*/
+pub struct RoutableSource<S> {
+    source: S,
+    fallback: Option<Box<dyn IpSource>>,
+}
+
+impl<S> RoutableSource<S>
+where
+    S: IpSource,
+{
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            fallback: None,
+        }
+    }
+
+    /**
+        Sets the source to ask when the inner source returns an address
+        that is not publicly routable.
+    */
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: impl IpSource + 'static) -> Self {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for RoutableSource<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoutableSource")
+            .field("source", &self.source)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<S> IpSource for RoutableSource<S>
+where
+    S: IpSource,
+{
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        // 1. Use the address from the inner source, if it is publicly routable
+        let Some(ip) = self.source.get_ip(family).await? else {
+            return Ok(None);
+        };
+        let class = AddressClass::of(ip);
+        if class.is_routable() {
+            return Ok(Some(ip));
+        }
+
+        // 2. Otherwise, ask the fallback source, which must also return a routable one
+        let Some(fallback) = &self.fallback else {
+            bail!("refusing to use {ip}, which is a {class} address and not publicly routable");
+        };
+        tracing::warn!(
+            %ip,
+            %class,
+            "IP source returned an address that is not publicly routable, using fallback source"
+        );
+        let Some(fallback_ip) = fallback
+            .get_ip(family)
+            .await
+            .context("failed to get address from fallback source")?
+        else {
+            return Ok(None);
+        };
+        let fallback_class = AddressClass::of(fallback_ip);
+        if !fallback_class.is_routable() {
+            bail!(
+                "refusing to use {fallback_ip} from fallback source, \
+                 which is a {fallback_class} address and not publicly routable"
+            );
+        }
+        Ok(Some(fallback_ip))
+    }
+
+    async fn changed(&self) -> Result<()> {
+        let Some(fallback) = &self.fallback else {
+            return self.source.changed().await;
+        };
+        let mut source = pin!(self.source.changed());
+        let mut fallback = pin!(fallback.changed());
+        poll_fn(|cx| match source.as_mut().poll(cx) {
+            Poll::Ready(result) => Poll::Ready(result),
+            Poll::Pending => fallback.as_mut().poll(cx),
+        })
+        .await
+    }
+
+    fn watches_changes(&self) -> bool {
+        // The address behind a NAT can change without the inner source noticing
+        self.source.watches_changes()
+            && self.fallback.as_ref().is_none_or(IpSource::watches_changes)
+    }
+}
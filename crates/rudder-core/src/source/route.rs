@@ -0,0 +1,120 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::{IpFamily, IpSource, is_unavailable, is_usable, network::NetworkWatcher};
+
+#[cfg(feature = "interface")]
+use super::InterfaceSource;
+
+const PROBE_V4: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 53);
+const PROBE_V6: SocketAddr = SocketAddr::new(
+    IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+    53,
+);
+
+/**
+    An [`IpSource`] that finds the local address this device would use
+    to reach the public internet, by asking the OS for a route to it.
+
+    No packets are sent - this only works as an external address when
+    the device is not behind NAT, which is common for IPv6, but rare
+    for IPv4. IPv6 addresses that are not globally routable, such as
+    link-local and unique local addresses, are never returned.
+
+    The OS prefers temporary (privacy extensions) IPv6 addresses for
+    outgoing connections, but those change too often to publish - when
+    the interface feature is enabled, a stable address on the same
+    interface is returned instead, on platforms that can tell them apart.
+
+    On Linux, changes to addresses and routes are detected
+    through netlink notifications, when that feature is enabled.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct RouteSource {
+    watcher: NetworkWatcher,
+}
+
+impl RouteSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IpSource for RouteSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        let (bind, probe) = match family {
+            IpFamily::V4 => (SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)), PROBE_V4),
+            IpFamily::V6 => (SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)), PROBE_V6),
+        };
+
+        // Connecting a UDP socket sends nothing, but makes the OS pick a
+        // route and source address - failures here mean no connectivity
+        let socket = match UdpSocket::bind(bind) {
+            Ok(socket) => socket,
+            Err(e) if is_unavailable(e.kind()) => return Ok(None),
+            Err(e) => return Err(e).context("failed to bind socket for route lookup"),
+        };
+        match socket.connect(probe) {
+            Ok(()) => {}
+            Err(e) if is_unavailable(e.kind()) => return Ok(None),
+            Err(e) => return Err(e).context("failed to look up route to the internet"),
+        }
+
+        let ip = socket
+            .local_addr()
+            .context("failed to get local address for route")?
+            .ip();
+        if !is_usable(ip) {
+            return Ok(None);
+        }
+
+        stable_address(ip)
+            .await
+            .context("failed to check if the address for the route is temporary")
+    }
+
+    async fn changed(&self) -> Result<()> {
+        self.watcher.changed().await
+    }
+
+    fn watches_changes(&self) -> bool {
+        NetworkWatcher::SUPPORTED
+    }
+}
+
+/**
+    Replaces a temporary or deprecated address that the OS picked for a route with
+    a stable address of the same family on the same interface, if there is one.
+*/
+#[cfg(feature = "interface")]
+async fn stable_address(ip: IpAddr) -> Result<Option<IpAddr>> {
+    let source = InterfaceSource::new();
+    let addresses = source.addresses().await?;
+    let Some(picked) = addresses.iter().find(|address| address.ip == ip) else {
+        return Ok(Some(ip));
+    };
+    if !picked.temporary && !picked.deprecated {
+        return Ok(Some(ip));
+    }
+
+    let family = IpFamily::from(ip);
+    Ok(addresses
+        .iter()
+        .find(|address| {
+            address.interface == picked.interface
+                && family.matches(address.ip)
+                && source.is_candidate(address)
+        })
+        .map(|address| address.ip))
+}
+
+/// Temporary addresses can only be told apart by listing interface addresses
+#[cfg(not(feature = "interface"))]
+#[allow(clippy::unused_async)]
+async fn stable_address(ip: IpAddr) -> Result<Option<IpAddr>> {
+    Ok(Some(ip))
+}
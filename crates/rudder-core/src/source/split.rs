@@ -0,0 +1,60 @@
+use std::{future::poll_fn, net::IpAddr, pin::pin, task::Poll};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{IpFamily, IpSource};
+
+/**
+    An [`IpSource`] that uses separate sources for IPv4 and IPv6 addresses.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SplitSource<V4, V6> {
+    v4: V4,
+    v6: V6,
+}
+
+impl<V4, V6> SplitSource<V4, V6>
+where
+    V4: IpSource,
+    V6: IpSource,
+{
+    pub fn new(v4: V4, v6: V6) -> Self {
+        Self { v4, v6 }
+    }
+}
+
+#[async_trait]
+impl<V4, V6> IpSource for SplitSource<V4, V6>
+where
+    V4: IpSource,
+    V6: IpSource,
+{
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        match family {
+            IpFamily::V4 => self.v4.get_ip(family).await,
+            IpFamily::V6 => self.v6.get_ip(family).await,
+        }
+    }
+
+    async fn changed(&self) -> Result<()> {
+        let mut v4 = pin!(self.v4.changed());
+        let mut v6 = pin!(self.v6.changed());
+        poll_fn(|cx| match v4.as_mut().poll(cx) {
+            Poll::Ready(result) => Poll::Ready(result),
+            Poll::Pending => v6.as_mut().poll(cx),
+        })
+        .await
+    }
+
+    fn watches_changes(&self) -> bool {
+        self.v4.watches_changes() && self.v6.watches_changes()
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        match family {
+            IpFamily::V4 => self.v4.origin(family).await,
+            IpFamily::V6 => self.v6.origin(family).await,
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use tokio::net::lookup_host;
+
+use super::{
+    IpFamily, IpSource,
+    udp::{self, random_bytes},
+};
+
+/// The STUN servers used by default, tried in order
+pub const DEFAULT_STUN_SERVERS: &[&str] = &["stun.cloudflare.com:3478", "stun.l.google.com:19302"];
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+const HEADER_LEN: usize = 20;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+const FAMILY_V4: u8 = 0x01;
+const FAMILY_V6: u8 = 0x02;
+
+/**
+    An [`IpSource`] that sends STUN Binding Requests ([RFC 5389]) to
+    public STUN servers, which respond with the address they saw the
+    request coming from - the external address of this device.
+
+    Servers are tried in order until one of them responds. Requests
+    are sent over UDP, from a socket of the requested address family,
+    so this works for both IPv4 and IPv6 as long as the server does.
+
+    [RFC 5389]: https://datatracker.ietf.org/doc/html/rfc5389
+*/
+#[derive(Debug, Clone)]
+pub struct StunSource {
+    servers: Vec<String>,
+    timeout: Duration,
+}
+
+impl StunSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            servers: DEFAULT_STUN_SERVERS
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /**
+        Sets the STUN servers to use, as `host:port` pairs.
+    */
+    #[must_use]
+    pub fn with_servers(mut self, servers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.servers = servers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /**
+        Sets how long to wait for each server to respond before timing out.
+    */
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn query(&self, server: &str, family: IpFamily) -> Result<Option<IpAddr>> {
+        // 1. Resolve the server, using only addresses in the requested family
+        let Some(addr) = lookup_host(server)
+            .await
+            .with_context(|| format!("failed to resolve stun server '{server}'"))?
+            .find(|addr| family.matches(addr.ip()))
+        else {
+            return Ok(None);
+        };
+
+        // 2. Connect a socket in the same family, no connectivity means no address
+        let Some(socket) = udp::connect(addr).await? else {
+            return Ok(None);
+        };
+
+        // 3. Send the request, retransmitting with a doubling interval
+        //    as described in the RFC, until we get a matching response
+        let transaction_id = random_bytes::<12>();
+        let request = encode_request(&transaction_id);
+        let ip = udp::exchange(&socket, &request, INITIAL_RTO, self.timeout, |packet| {
+            decode_response(packet, &transaction_id)
+        })
+        .await
+        .with_context(|| format!("failed to query stun server '{server}'"))?;
+
+        Ok(Some(ip))
+    }
+}
+
+impl Default for StunSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl IpSource for StunSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        let mut last_error = None;
+        for server in &self.servers {
+            match self.query(server, family).await {
+                Ok(Some(ip)) => return Ok(Some(ip)),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::debug!(%server, "Failed to query stun server: {e:#}");
+                    last_error.replace(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) => Err(e.context("no stun server responded")),
+            None => Ok(None),
+        }
+    }
+}
+
+fn encode_request(transaction_id: &[u8; 12]) -> [u8; HEADER_LEN] {
+    let mut request = [0u8; HEADER_LEN];
+    request[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request[2..4].copy_from_slice(&0u16.to_be_bytes());
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..20].copy_from_slice(transaction_id);
+    request
+}
+
+/**
+    Decodes a STUN response, returning the mapped address in it.
+
+    Returns `None` for packets that are not a response to our request,
+    which should be ignored, and an error for error responses.
+*/
+fn decode_response(packet: &[u8], transaction_id: &[u8; 12]) -> Result<Option<IpAddr>> {
+    if packet.len() < HEADER_LEN
+        || packet[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || packet[8..20] != *transaction_id
+    {
+        return Ok(None);
+    }
+
+    let kind = u16::from_be_bytes([packet[0], packet[1]]);
+    let len = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+    let Some(mut attrs) = packet.get(HEADER_LEN..HEADER_LEN + len) else {
+        bail!("stun response is truncated");
+    };
+
+    let mut mapped = None;
+    let mut xor_mapped = None;
+    let mut error = None;
+    while attrs.len() >= 4 {
+        let attr_kind = u16::from_be_bytes([attrs[0], attrs[1]]);
+        let attr_len = usize::from(u16::from_be_bytes([attrs[2], attrs[3]]));
+        let Some(value) = attrs.get(4..4 + attr_len) else {
+            bail!("stun response attribute is truncated");
+        };
+        match attr_kind {
+            ATTR_MAPPED_ADDRESS => mapped = decode_address(value, None),
+            ATTR_XOR_MAPPED_ADDRESS => xor_mapped = decode_address(value, Some(transaction_id)),
+            ATTR_ERROR_CODE if value.len() >= 4 => {
+                let code = u16::from(value[2] & 0x07) * 100 + u16::from(value[3]);
+                let reason = String::from_utf8_lossy(&value[4..]).trim().to_string();
+                error = Some(format!("{code} {reason}"));
+            }
+            _ => {}
+        }
+        // Attributes are padded to a multiple of 4 bytes
+        let padded = (4 + attr_len).next_multiple_of(4);
+        attrs = attrs.get(padded..).unwrap_or_default();
+    }
+
+    match kind {
+        BINDING_SUCCESS => match xor_mapped.or(mapped) {
+            Some(ip) => Ok(Some(ip)),
+            None => bail!("stun response did not contain a mapped address"),
+        },
+        BINDING_ERROR => bail!(
+            "stun server responded with an error: {}",
+            error.as_deref().unwrap_or("unknown error")
+        ),
+        _ => Ok(None),
+    }
+}
+
+fn decode_address(value: &[u8], xor_with: Option<&[u8; 12]>) -> Option<IpAddr> {
+    let family = *value.get(1)?;
+    let mut mask = [0u8; 16];
+    if let Some(transaction_id) = xor_with {
+        mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        mask[4..].copy_from_slice(transaction_id);
+    }
+    match family {
+        FAMILY_V4 => {
+            let bytes: [u8; 4] = value.get(4..8)?.try_into().ok()?;
+            let bytes: [u8; 4] = std::array::from_fn(|i| bytes[i] ^ mask[i]);
+            Some(IpAddr::V4(Ipv4Addr::from(bytes)))
+        }
+        FAMILY_V6 => {
+            let bytes: [u8; 16] = value.get(4..20)?.try_into().ok()?;
+            let bytes: [u8; 16] = std::array::from_fn(|i| bytes[i] ^ mask[i]);
+            Some(IpAddr::V6(Ipv6Addr::from(bytes)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::net::UdpSocket;
+
+    use super::*;
+
+    const MAPPED_V4: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    const MAPPED_V6: IpAddr = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x42));
+
+    /// Encodes a Binding Success Response with an XOR-MAPPED-ADDRESS attribute
+    fn encode_response(transaction_id: &[u8], ip: IpAddr) -> Vec<u8> {
+        let mut mask = MAGIC_COOKIE.to_be_bytes().to_vec();
+        mask.extend_from_slice(transaction_id);
+        let (family, octets) = match ip {
+            IpAddr::V4(ip) => (FAMILY_V4, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (FAMILY_V6, ip.octets().to_vec()),
+        };
+
+        let mut value = vec![0, family];
+        // The port is never decoded, so it is left as zero
+        value.extend_from_slice(&[0, 0]);
+        value.extend(octets.iter().zip(&mask).map(|(byte, mask)| byte ^ mask));
+
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&BINDING_SUCCESS.to_be_bytes());
+        packet.extend_from_slice(&u16::try_from(4 + value.len()).unwrap().to_be_bytes());
+        packet.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        packet.extend_from_slice(transaction_id);
+        packet.extend_from_slice(&ATTR_XOR_MAPPED_ADDRESS.to_be_bytes());
+        packet.extend_from_slice(&u16::try_from(value.len()).unwrap().to_be_bytes());
+        packet.extend_from_slice(&value);
+        packet
+    }
+
+    /**
+        Binds a local STUN responder, which answers every Binding Request
+        with the packets returned for its transaction ID, if any.
+    */
+    async fn responder(
+        bind: &str,
+        respond: impl Fn(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+    ) -> SocketAddr {
+        let socket = UdpSocket::bind(bind).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1100];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                assert_eq!(len, HEADER_LEN);
+                assert_eq!(buf[0..2], BINDING_REQUEST.to_be_bytes());
+                assert_eq!(buf[4..8], MAGIC_COOKIE.to_be_bytes());
+                for packet in respond(&buf[8..20]) {
+                    socket.send_to(&packet, peer).await.unwrap();
+                }
+            }
+        });
+        addr
+    }
+
+    fn source(addr: SocketAddr) -> StunSource {
+        StunSource::new()
+            .with_servers([addr.to_string()])
+            .with_timeout(Duration::from_millis(300))
+    }
+
+    #[tokio::test]
+    async fn decodes_xor_mapped_ipv4_address() {
+        let addr = responder("127.0.0.1:0", |id| vec![encode_response(id, MAPPED_V4)]).await;
+        let ip = source(addr).get_ip(IpFamily::V4).await.unwrap();
+        assert_eq!(ip, Some(MAPPED_V4));
+    }
+
+    #[tokio::test]
+    async fn decodes_xor_mapped_ipv6_address() {
+        let addr = responder("[::1]:0", |id| vec![encode_response(id, MAPPED_V6)]).await;
+        let ip = source(addr).get_ip(IpFamily::V6).await.unwrap();
+        assert_eq!(ip, Some(MAPPED_V6));
+    }
+
+    #[tokio::test]
+    async fn ignores_responses_for_other_transactions() {
+        let addr = responder("127.0.0.1:0", |id| {
+            let mut other = id.to_vec();
+            other[0] ^= 0xff;
+            vec![
+                encode_response(&other, MAPPED_V6),
+                encode_response(id, MAPPED_V4),
+            ]
+        })
+        .await;
+        let ip = source(addr).get_ip(IpFamily::V4).await.unwrap();
+        assert_eq!(ip, Some(MAPPED_V4));
+    }
+
+    #[tokio::test]
+    async fn times_out_without_matching_response() {
+        let addr = responder("127.0.0.1:0", |id| {
+            let mut other = id.to_vec();
+            other[11] ^= 0xff;
+            vec![encode_response(&other, MAPPED_V4)]
+        })
+        .await;
+        let error = source(addr).get_ip(IpFamily::V4).await.unwrap_err();
+        assert!(format!("{error:#}").contains("timed out"), "{error:#}");
+    }
+}
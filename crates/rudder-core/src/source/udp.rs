@@ -0,0 +1,111 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    io::ErrorKind,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use tokio::{
+    net::UdpSocket,
+    time::{Instant, timeout_at},
+};
+
+use super::is_unavailable;
+
+/**
+    Binds a UDP socket in the same address family as the given
+    address, and connects it so that only the address can talk to it.
+
+    Returns `None` if there is no connectivity for the address family.
+*/
+pub(crate) async fn connect(addr: SocketAddr) -> Result<Option<UdpSocket>> {
+    let bind = match addr {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = match UdpSocket::bind(bind).await {
+        Ok(socket) => socket,
+        Err(e) if is_unavailable(e.kind()) => return Ok(None),
+        Err(e) => return Err(e).context("failed to bind udp socket"),
+    };
+    match socket.connect(addr).await {
+        Ok(()) => Ok(Some(socket)),
+        Err(e) if is_unavailable(e.kind()) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to connect to '{addr}'")),
+    }
+}
+
+/**
+    Sends a request on a connected UDP socket, and waits for a response.
+
+    The request is retransmitted with a doubling interval, starting at
+    `initial_rto`, until the `decode` function accepts a response by
+    returning `Some`, or until the timeout is reached.
+*/
+pub(crate) async fn exchange<T>(
+    socket: &UdpSocket,
+    request: &[u8],
+    initial_rto: Duration,
+    timeout: Duration,
+    mut decode: impl FnMut(&[u8]) -> Result<Option<T>>,
+) -> Result<T> {
+    let peer = socket.peer_addr().context("udp socket is not connected")?;
+    let deadline = Instant::now() + timeout;
+    let mut rto = initial_rto;
+    let mut buf = [0u8; 1100];
+    loop {
+        match socket.send(request).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                bail!("'{peer}' refused the connection")
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to send to '{peer}'")),
+        }
+
+        let retransmit_at = (Instant::now() + rto).min(deadline);
+        rto *= 2;
+
+        loop {
+            match timeout_at(retransmit_at, socket.recv(&mut buf)).await {
+                Ok(Ok(len)) => {
+                    if let Some(response) = decode(&buf[..len])? {
+                        return Ok(response);
+                    }
+                }
+                Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => {
+                    bail!("'{peer}' refused the connection")
+                }
+                Ok(Err(e)) => {
+                    return Err(e).with_context(|| format!("failed to receive from '{peer}'"));
+                }
+                Err(_) => break,
+            }
+        }
+
+        if Instant::now() >= deadline {
+            bail!("timed out waiting for a response from '{peer}'");
+        }
+    }
+}
+
+/**
+    Generates random bytes for transaction IDs and nonces.
+
+    These only need to be unpredictable enough to not be spoofed
+    easily, and the randomly seeded std hasher is plenty for that.
+*/
+pub(crate) fn random_bytes<const N: usize>() -> [u8; N] {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let state = RandomState::new();
+    let mut bytes = [0u8; N];
+    for (index, chunk) in bytes.chunks_mut(8).enumerate() {
+        let hash = state.hash_one((nanos, index)).to_be_bytes();
+        chunk.copy_from_slice(&hash[..chunk.len()]);
+    }
+    bytes
+}
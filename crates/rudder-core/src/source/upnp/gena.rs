@@ -0,0 +1,319 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use igd_next::aio::{Gateway, tokio::Tokio};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::{Instant, sleep_until, timeout},
+};
+
+/// How long to ask the gateway to keep our subscription for
+const SUBSCRIPTION_SECONDS: u64 = 1800;
+/// How long to wait for the gateway to respond to a request, or to send a full event
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The largest request or response that we accept from the gateway
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/**
+    A subscription to uPnP GENA events for the WAN connection service
+    of a gateway, which sends events whenever the external address changes.
+
+    The subscription is renewed automatically, as long as
+    [`GenaSubscription::changed`] is being waited on.
+*/
+#[derive(Debug)]
+pub(super) struct GenaSubscription {
+    listener: TcpListener,
+    gateway: SocketAddr,
+    event_path: String,
+    callback: String,
+    sid: String,
+    renew_at: Instant,
+}
+
+impl GenaSubscription {
+    /**
+        Subscribes to events from the WAN connection service of the given gateway.
+    */
+    pub(super) async fn new(gateway: &Gateway<Tokio>) -> Result<Self> {
+        // 1. Find the event URL for the service that we got the control URL for
+        let description = request(gateway.addr, "GET", &gateway.root_url, &[])
+            .await
+            .context("failed to get gateway device description")?;
+        let event_path = find_event_path(&description.body, &gateway.control_url)
+            .context("gateway does not support eventing for its WAN connection")?;
+
+        // 2. Listen for events on the local address that can reach the gateway
+        let local = TcpStream::connect(gateway.addr)
+            .await
+            .context("failed to connect to gateway")?
+            .local_addr()
+            .context("failed to get local address for gateway")?;
+        let listener = TcpListener::bind(SocketAddr::new(local.ip(), 0))
+            .await
+            .context("failed to listen for gateway events")?;
+        let callback = match listener.local_addr()? {
+            SocketAddr::V4(addr) => format!("<http://{addr}/>"),
+            SocketAddr::V6(addr) => format!("<http://[{}]:{}/>", addr.ip(), addr.port()),
+        };
+
+        // 3. Subscribe, which makes the gateway send an initial event right away
+        let mut subscription = Self {
+            listener,
+            gateway: gateway.addr,
+            event_path,
+            callback,
+            sid: String::new(),
+            renew_at: Instant::now(),
+        };
+        subscription.subscribe(false).await?;
+        tracing::debug!(
+            gateway = %subscription.gateway,
+            sid = %subscription.sid,
+            "Subscribed to gateway / router events"
+        );
+
+        Ok(subscription)
+    }
+
+    /**
+        Waits for the gateway to send an event for the external address.
+    */
+    pub(super) async fn changed(&mut self) -> Result<()> {
+        loop {
+            tokio::select! {
+                () = sleep_until(self.renew_at) => {
+                    // Gateways forget subscriptions when restarting,
+                    // so subscribe from scratch if renewing fails
+                    if let Err(e) = self.subscribe(true).await {
+                        tracing::debug!("Failed to renew gateway subscription: {e:#}");
+                        self.subscribe(false).await?;
+                    }
+                }
+                accepted = self.listener.accept() => {
+                    let (stream, _) = accepted.context("failed to accept gateway event")?;
+                    match timeout(REQUEST_TIMEOUT, self.handle_event(stream)).await {
+                        Ok(Ok(true)) => return Ok(()),
+                        Ok(Ok(false)) => {}
+                        Ok(Err(e)) => tracing::debug!("Failed to handle gateway event: {e:#}"),
+                        Err(_) => tracing::debug!("Timed out while handling gateway event"),
+                    }
+                }
+            }
+        }
+    }
+
+    async fn subscribe(&mut self, renew: bool) -> Result<()> {
+        let timeout = format!("Second-{SUBSCRIPTION_SECONDS}");
+        let headers = if renew {
+            vec![("SID", self.sid.as_str()), ("TIMEOUT", timeout.as_str())]
+        } else {
+            vec![
+                ("CALLBACK", self.callback.as_str()),
+                ("NT", "upnp:event"),
+                ("TIMEOUT", timeout.as_str()),
+            ]
+        };
+
+        let response = request(self.gateway, "SUBSCRIBE", &self.event_path, &headers)
+            .await
+            .context("failed to subscribe to gateway events")?;
+        if response.status != 200 {
+            bail!(
+                "gateway refused event subscription with status {}",
+                response.status
+            );
+        }
+
+        if let Some(sid) = response.header("SID") {
+            self.sid = sid.to_string();
+        } else if !renew {
+            bail!("gateway did not respond with a subscription id");
+        }
+
+        // Renew at half of the granted duration, to leave plenty of margin
+        let seconds = response
+            .header("TIMEOUT")
+            .and_then(|t| t.strip_prefix("Second-"))
+            .and_then(|t| t.parse::<u64>().ok())
+            .unwrap_or(SUBSCRIPTION_SECONDS)
+            .clamp(60, SUBSCRIPTION_SECONDS);
+        self.renew_at = Instant::now() + Duration::from_secs(seconds / 2);
+
+        Ok(())
+    }
+
+    async fn handle_event(&self, stream: TcpStream) -> Result<bool> {
+        let mut reader = BufReader::new(stream);
+        let message = read_message(&mut reader).await?;
+
+        let mut stream = reader.into_inner();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await?;
+
+        Ok(message.start.starts_with("NOTIFY ")
+            && message.header("SID") == Some(self.sid.as_str())
+            && message.body.contains("ExternalIPAddress"))
+    }
+}
+
+/// A minimal HTTP message, either a request or a response
+struct Message {
+    start: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Message {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/**
+    Sends a minimal HTTP request to the gateway - its HTTP server
+    is on the local network and only ever speaks plain HTTP.
+*/
+async fn request(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> Result<Message> {
+    let host = match addr.ip() {
+        IpAddr::V4(ip) => format!("{ip}:{}", addr.port()),
+        IpAddr::V6(ip) => format!("[{ip}]:{}", addr.port()),
+    };
+    let headers = headers
+        .iter()
+        .map(|(name, value)| [name, ": ", value, "\r\n"].concat())
+        .collect::<String>();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHOST: {host}\r\nConnection: close\r\n{headers}Content-Length: 0\r\n\r\n"
+    );
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(request.as_bytes()).await?;
+        read_message(&mut BufReader::new(stream)).await
+    };
+    timeout(REQUEST_TIMEOUT, exchange)
+        .await
+        .with_context(|| format!("timed out waiting for '{host}' to respond"))?
+}
+
+async fn read_message(reader: &mut BufReader<TcpStream>) -> Result<Message> {
+    // 1. Read the start line and headers, until an empty line
+    let mut start = String::new();
+    reader.read_line(&mut start).await?;
+    let start = start.trim_end().to_string();
+    if start.is_empty() {
+        bail!("connection closed before receiving a message");
+    }
+
+    let mut headers = Vec::new();
+    let mut total = start.len();
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).await?;
+        total += read;
+        if total > MAX_MESSAGE_LEN {
+            bail!("message is too large");
+        }
+        let line = line.trim_end();
+        if read == 0 || line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let status = start
+        .strip_prefix("HTTP/1.")
+        .and_then(|rest| rest.get(2..5))
+        .and_then(|code| code.parse().ok())
+        .unwrap_or_default();
+    let mut message = Message {
+        start,
+        status,
+        headers,
+        body: String::new(),
+    };
+
+    // 2. Read the body, which may be chunked or have a known length
+    let chunked = message
+        .header("Transfer-Encoding")
+        .is_some_and(|t| t.eq_ignore_ascii_case("chunked"));
+    let length = message
+        .header("Content-Length")
+        .and_then(|l| l.parse::<usize>().ok());
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size).await?;
+            let size = size.trim().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size, 16).context("invalid chunk size")?;
+            if size == 0 || body.len() + size > MAX_MESSAGE_LEN {
+                break;
+            }
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk).await?;
+            body.extend_from_slice(&chunk[..size]);
+        }
+    } else if let Some(length) = length {
+        if length > MAX_MESSAGE_LEN {
+            bail!("message is too large");
+        }
+        body.resize(length, 0);
+        reader.read_exact(&mut body).await?;
+    } else if !message.start.starts_with("NOTIFY ") && !message.start.starts_with("SUBSCRIBE ") {
+        // Responses without a length end when the connection closes
+        reader
+            .take(MAX_MESSAGE_LEN as u64)
+            .read_to_end(&mut body)
+            .await?;
+    }
+
+    message.body = String::from_utf8_lossy(&body).into_owned();
+    Ok(message)
+}
+
+/**
+    Finds the event subscription path for the service with the given
+    control URL, in an XML device description from the gateway.
+*/
+fn find_event_path(description: &str, control_url: &str) -> Option<String> {
+    let service = description
+        .split("<service>")
+        .skip(1)
+        .map(|s| s.split("</service>").next().unwrap_or_default())
+        .find(|s| tag_value(s, "controlURL").is_some_and(|url| url_path(url) == control_url))?;
+    let url = tag_value(service, "eventSubURL")?;
+    let path = url_path(url);
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+fn tag_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    Some(xml[start..end].trim())
+}
+
+fn url_path(url: &str) -> &str {
+    // URLs in descriptions may be absolute, but always point to the gateway itself
+    match url.strip_prefix("http://") {
+        Some(rest) => rest.find('/').map_or("/", |index| &rest[index..]),
+        None => url,
+    }
+}
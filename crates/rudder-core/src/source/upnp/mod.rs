@@ -0,0 +1,160 @@
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use igd_next::{
+    SearchOptions,
+    aio::{
+        Gateway,
+        tokio::{Tokio, search_gateway},
+    },
+};
+use tokio::{sync::Mutex, time::timeout};
+
+use super::{IpFamily, IpSource};
+
+mod gena;
+
+use self::gena::GenaSubscription;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+    An [`IpSource`] that finds the current gateway / router
+    through uPnP / IGD, and asks it for the external IP address.
+
+    Gateways only report an external IPv4 address through IGD,
+    so this source never returns any IPv6 addresses.
+
+    Changes are detected by subscribing to uPnP GENA events from
+    the gateway, which most gateways send when their address changes.
+
+    The gateway is only searched for once, and then remembered until
+    it stops responding, to avoid a multicast search and a device
+    description fetch every time the address is requested.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct UpnpSource {
+    timeout: Option<Duration>,
+    gateway: Arc<Mutex<Option<Gateway<Tokio>>>>,
+    subscription: Arc<Mutex<Option<GenaSubscription>>>,
+}
+
+impl UpnpSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Sets how long to search for the gateway / router before timing out.
+    */
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    async fn search(&self) -> Result<Gateway<Tokio>> {
+        let mut options = SearchOptions::default();
+        if let Some(timeout) = self.timeout {
+            options.timeout = Some(timeout);
+        }
+
+        let gateway = search_gateway(options)
+            .await
+            .context("failed to find gateway / router through uPnP")?;
+        tracing::debug!(gateway = %gateway.addr, "Found gateway / router");
+
+        Ok(gateway)
+    }
+
+    /// Gets the remembered gateway, or searches for it and remembers it
+    async fn gateway(&self) -> Result<Gateway<Tokio>> {
+        let mut cached = self.gateway.lock().await;
+        if let Some(gateway) = cached.as_ref() {
+            return Ok(gateway.clone());
+        }
+        let gateway = self.search().await?;
+        Ok(cached.insert(gateway).clone())
+    }
+
+    async fn forget_gateway(&self) {
+        self.gateway.lock().await.take();
+    }
+}
+
+async fn external_ip(gateway: &Gateway<Tokio>) -> Result<IpAddr> {
+    timeout(REQUEST_TIMEOUT, gateway.get_external_ip())
+        .await
+        .context("timed out getting external ip through gateway")?
+        .context("failed to get external ip through gateway")
+}
+
+#[async_trait]
+impl IpSource for UpnpSource {
+    async fn get_ip(&self, family: IpFamily) -> Result<Option<IpAddr>> {
+        if family != IpFamily::V4 {
+            return Ok(None);
+        }
+
+        // 1. Ask the remembered gateway, if it stops responding
+        //    it may have rebooted or changed, so search again
+        let cached = self.gateway.lock().await.clone();
+        if let Some(gateway) = cached {
+            match external_ip(&gateway).await {
+                Ok(ip) => return Ok(family.matches(ip).then_some(ip)),
+                Err(e) => {
+                    tracing::debug!(
+                        gateway = %gateway.addr,
+                        "Gateway stopped responding, searching again: {e:#}"
+                    );
+                    self.forget_gateway().await;
+                }
+            }
+        }
+
+        // 2. Search for the gateway, and only remember it if it responds
+        let gateway = self.search().await?;
+        let ip = external_ip(&gateway).await?;
+        self.gateway.lock().await.replace(gateway);
+
+        Ok(family.matches(ip).then_some(ip))
+    }
+
+    async fn origin(&self, family: IpFamily) -> Option<String> {
+        if family != IpFamily::V4 {
+            return None;
+        }
+        let gateway = self.gateway.lock().await;
+        gateway
+            .as_ref()
+            .map(|gateway| format!("upnp ({})", gateway.addr.ip()))
+    }
+
+    async fn changed(&self) -> Result<()> {
+        let mut guard = self.subscription.lock().await;
+        let subscription = if let Some(subscription) = guard.as_mut() {
+            subscription
+        } else {
+            let gateway = self.gateway().await?;
+            match GenaSubscription::new(&gateway).await {
+                Ok(subscription) => guard.insert(subscription),
+                Err(e) => {
+                    self.forget_gateway().await;
+                    return Err(e);
+                }
+            }
+        };
+        let result = subscription.changed().await;
+        if result.is_err() {
+            guard.take();
+            self.forget_gateway().await;
+        }
+        result
+    }
+
+    fn watches_changes(&self) -> bool {
+        true
+    }
+}
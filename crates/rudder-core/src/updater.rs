@@ -0,0 +1,539 @@
+#![allow(clippy::missing_errors_doc)]
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    net::IpAddr,
+    sync::Arc,
+};
+
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+#[cfg(feature = "tokio")]
+use tokio::time::{Interval, MissedTickBehavior};
+
+#[cfg(feature = "tokio")]
+use crate::retry::{Backoff, is_fatal};
+use crate::{
+    provider::{DnsProvider, DnsRecord, DnsRecordChange, DnsRecordKind, RecordOptions},
+    source::{IpFamily, IpSource},
+};
+
+/// The default interval for polling, when the IP source detects changes by itself
+#[cfg(feature = "tokio")]
+const DEFAULT_FALLBACK_INTERVAL: Duration = Duration::from_mins(5);
+
+/// The result of reconciling a single address family in [`Updater::reconcile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reconciled {
+    /// The address family that was reconciled
+    pub family: IpFamily,
+    /// The IP address that was found using the IP source, if any
+    pub ip: Option<IpAddr>,
+    /// The change that was made to DNS records, if any
+    pub change: DnsRecordChange,
+}
+
+/**
+    The context attached to errors from the IP source in [`Updater::reconcile`],
+    which makes it possible to tell them apart from errors from the DNS provider.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpSourceError {
+    /// The address family that an address could not be found for
+    pub family: IpFamily,
+}
+
+impl Display for IpSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to get current {} address", self.family)
+    }
+}
+
+impl Error for IpSourceError {}
+
+/**
+    The error returned by [`Updater::reconcile`] when at least one of the address
+    families failed to reconcile - every family is still attempted, so that a
+    failure for one of them never keeps the records for the others from updating.
+*/
+#[derive(Debug)]
+pub struct ReconcileError {
+    /// The results for the address families that were reconciled successfully
+    pub reconciled: Vec<Reconciled>,
+    /// The errors for the address families that failed to reconcile, in order
+    pub failures: Vec<(IpFamily, anyhow::Error)>,
+}
+
+impl ReconcileError {
+    /**
+        Gets the errors for all of the address families that failed to reconcile.
+    */
+    pub fn errors(&self) -> impl Iterator<Item = &anyhow::Error> {
+        self.failures.iter().map(|(_, error)| error)
+    }
+}
+
+impl Display for ReconcileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (family, error)) in self.failures.iter().enumerate() {
+            if index > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{family}: {error:#}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ReconcileError {}
+
+/// The health of an [`Updater`], as reported by [`Updater::health`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// No reconciliation step has finished yet
+    Starting,
+    /// The last reconciliation step succeeded
+    Healthy,
+    /// The last reconciliation steps failed, and are being retried
+    Unhealthy {
+        /// How many reconciliation steps failed in a row
+        failures: u32,
+        /// The error from the last failed reconciliation step
+        error: String,
+    },
+}
+
+/// What was last successfully published for an address family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Published {
+    Address(IpAddr),
+    Deleted,
+}
+
+/// A callback for [`Updater::with_on_published`]
+type OnPublishedFn<S, P> = dyn Fn(&Updater<S, P>) + Send + Sync;
+
+struct OnPublished<S, P>(Arc<OnPublishedFn<S, P>>);
+
+impl<S, P> Clone for OnPublished<S, P> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<S, P> fmt::Debug for OnPublished<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnPublished")
+    }
+}
+
+/**
+    An update engine that keeps the A and AAAA records for a single
+    hostname in sync with the IP addresses from an [`IpSource`],
+    using the given [`DnsProvider`].
+
+    Each address family is reconciled independently, and records are
+    only looked up and changed when the IP address for a family differs
+    from the last one that was successfully published.
+*/
+#[derive(Debug, Clone)]
+pub struct Updater<S, P> {
+    source: S,
+    provider: P,
+    hostname: String,
+    options: RecordOptions,
+    families: Vec<IpFamily>,
+    delete_missing: bool,
+    published: HashMap<IpFamily, Published>,
+    records: HashMap<IpFamily, DnsRecord>,
+    on_published: Option<OnPublished<S, P>>,
+    health: Health,
+    #[cfg(feature = "tokio")]
+    fallback_interval: Duration,
+    #[cfg(feature = "tokio")]
+    backoff: Backoff,
+}
+
+impl<S, P> Updater<S, P>
+where
+    S: IpSource,
+    P: DnsProvider,
+{
+    /**
+        Creates a new updater for the given hostname.
+
+        By default, both IPv4 and IPv6 addresses are reconciled, and
+        records are never deleted when a family has no address.
+    */
+    pub fn new(source: S, provider: P, hostname: impl Into<String>) -> Self {
+        Self {
+            source,
+            provider,
+            hostname: hostname.into(),
+            options: RecordOptions::default(),
+            families: IpFamily::ALL.to_vec(),
+            delete_missing: false,
+            published: HashMap::new(),
+            records: HashMap::new(),
+            on_published: None,
+            health: Health::Starting,
+            #[cfg(feature = "tokio")]
+            fallback_interval: DEFAULT_FALLBACK_INTERVAL,
+            #[cfg(feature = "tokio")]
+            backoff: Backoff::default(),
+        }
+    }
+
+    /**
+        Sets the options to use for new records created by this updater,
+        and for changing existing records, such as which of them it owns.
+    */
+    #[must_use]
+    pub fn with_options(mut self, options: RecordOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /**
+        Sets which address families to reconcile records for.
+    */
+    #[must_use]
+    pub fn with_families(mut self, families: impl IntoIterator<Item = IpFamily>) -> Self {
+        self.families = families.into_iter().collect();
+        self.families.dedup();
+        self
+    }
+
+    /**
+        Sets whether to delete the records for an address family when the
+        IP source has no address for it - for example when IPv6 connectivity
+        goes away, and the AAAA record should no longer be published.
+    */
+    #[must_use]
+    pub fn with_delete_missing(mut self, delete_missing: bool) -> Self {
+        self.delete_missing = delete_missing;
+        self
+    }
+
+    /**
+        Sets the IP address that was last published for the given address family,
+        such as one remembered from a previous run, so that records are not
+        looked up again until the address changes.
+    */
+    #[must_use]
+    pub fn with_last_ip(mut self, family: IpFamily, ip: IpAddr) -> Self {
+        if family.matches(ip) {
+            self.published.insert(family, Published::Address(ip));
+        }
+        self
+    }
+
+    /**
+        Sets the DNS record that was last published for the given address family,
+        such as one remembered from a previous run, to report in [`Updater::last_record`].
+
+        Records are always looked up again when the address changes, so that
+        records that were taken over by hand, or duplicates that were added
+        since, are handled according to the [`RecordOptions`].
+    */
+    #[must_use]
+    pub fn with_last_record(mut self, family: IpFamily, record: DnsRecord) -> Self {
+        let matches = record.kind == DnsRecordKind::from(family)
+            && record.name == self.hostname
+            && !record.id.is_empty();
+        if matches {
+            self.records.insert(family, record);
+        }
+        self
+    }
+
+    /**
+        Sets a callback to invoke whenever this updater has published something
+        new for an address family, such as for persisting what was published.
+    */
+    #[must_use]
+    pub fn with_on_published(mut self, f: impl Fn(&Self) + Send + Sync + 'static) -> Self {
+        self.on_published = Some(OnPublished(Arc::new(f)));
+        self
+    }
+
+    /**
+        Sets how often to poll in [`Updater::run`] when the IP source detects
+        changes by itself, as a safety net for any missed changes - 5 minutes by default.
+    */
+    #[cfg(feature = "tokio")]
+    #[must_use]
+    pub fn with_fallback_interval(mut self, fallback_interval: Duration) -> Self {
+        self.fallback_interval = fallback_interval;
+        self
+    }
+
+    /**
+        Sets the backoff to use in [`Updater::run`] when retrying
+        failed reconciliation steps - from 5 seconds up to 5 minutes by default.
+    */
+    #[cfg(feature = "tokio")]
+    #[must_use]
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /**
+        Gets the hostname that this updater manages records for.
+    */
+    #[must_use]
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    /**
+        Gets the current health of this updater, based on
+        the results of the last reconciliation steps.
+    */
+    #[must_use]
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    /**
+        Gets the last IP address that was successfully published
+        for the given address family, if any.
+    */
+    #[must_use]
+    pub fn last_ip(&self, family: IpFamily) -> Option<IpAddr> {
+        match self.published.get(&family) {
+            Some(Published::Address(ip)) => Some(*ip),
+            _ => None,
+        }
+    }
+
+    /**
+        Gets the DNS record that was last published
+        for the given address family, if it is known.
+    */
+    #[must_use]
+    pub fn last_record(&self, family: IpFamily) -> Option<&DnsRecord> {
+        self.records.get(&family)
+    }
+
+    /**
+        Runs a single reconciliation step - gets the current IP addresses from
+        the IP source, and updates, creates, or deletes DNS records if they changed.
+
+        Returns one result for each of the address families being reconciled. Every
+        family is reconciled independently, and if any of them fail, a [`ReconcileError`]
+        is returned with the errors for those, and the results for all others.
+    */
+    pub async fn reconcile(&mut self) -> Result<Vec<Reconciled>> {
+        let mut reconciled = Vec::with_capacity(self.families.len());
+        let mut failures = Vec::new();
+        for family in self.families.clone() {
+            match self.reconcile_family(family).await {
+                Ok(result) => reconciled.push(result),
+                Err(e) => failures.push((family, e)),
+            }
+        }
+        if failures.is_empty() {
+            Ok(reconciled)
+        } else {
+            Err(ReconcileError {
+                reconciled,
+                failures,
+            }
+            .into())
+        }
+    }
+
+    async fn reconcile_family(&mut self, family: IpFamily) -> Result<Reconciled> {
+        // 1. Get the current IP address for this family from our source
+        let ip = self
+            .source
+            .get_ip(family)
+            .await
+            .context(IpSourceError { family })?;
+
+        let desired = match ip {
+            Some(ip) => Published::Address(ip),
+            None if self.delete_missing => Published::Deleted,
+            None => {
+                return Ok(Reconciled {
+                    family,
+                    ip,
+                    change: DnsRecordChange::Unchanged,
+                });
+            }
+        };
+
+        // 2. Skip any lookups if we already published this
+        if self.published.get(&family) == Some(&desired) {
+            return Ok(Reconciled {
+                family,
+                ip,
+                change: DnsRecordChange::Unchanged,
+            });
+        }
+
+        // 3. Update, create, or delete the DNS records
+        let change = match desired {
+            Published::Address(ip) => {
+                tracing::info!(
+                    ip = %ip,
+                    hostname = %self.hostname,
+                    "Updating DNS records with current IP"
+                );
+                self.publish_address(family, ip).await?
+            }
+            Published::Deleted => {
+                tracing::info!(
+                    hostname = %self.hostname,
+                    "No current {family} address, removing DNS records"
+                );
+                let change = self
+                    .provider
+                    .delete_addresses(&self.hostname, DnsRecordKind::from(family), &self.options)
+                    .await?;
+                self.records.remove(&family);
+                change
+            }
+        };
+        self.published.insert(family, desired);
+        if let Some(on_published) = &self.on_published {
+            (on_published.0)(self);
+        }
+
+        Ok(Reconciled { family, ip, change })
+    }
+
+    async fn publish_address(&mut self, family: IpFamily, ip: IpAddr) -> Result<DnsRecordChange> {
+        // Never update the record that was published last time without looking it up,
+        // since its owner may have changed, and duplicates may have been added
+        self.records.remove(&family);
+        let (change, record) = self
+            .provider
+            .upsert_address(&self.hostname, ip, &self.options)
+            .await?;
+        if let Some(record) = record {
+            self.records.insert(family, record);
+        }
+        Ok(change)
+    }
+
+    /**
+        Runs reconciliation steps forever, once every `interval`.
+
+        If the IP source detects changes by itself, reconciliation steps
+        run as soon as a change is detected instead, and polling only
+        happens once every fallback interval. If detecting changes fails,
+        this falls back to polling once every `interval` again. Whether the
+        source detects changes is checked again after every step, since some
+        sources only know once they found an address.
+
+        Failed reconciliation steps are retried with exponential backoff,
+        and the health of the updater is updated and logged accordingly.
+        Only returns if a reconciliation step fails with a fatal error,
+        such as when the API token for the provider is invalid.
+    */
+    #[cfg(feature = "tokio")]
+    pub async fn run(&mut self, interval: Duration) -> Result<()> {
+        let mut watching = self.source.watches_changes();
+        let mut watch_failed = false;
+        let mut ticker = ticker(if watching {
+            self.fallback_interval
+        } else {
+            interval
+        });
+
+        loop {
+            if watching {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    result = self.source.changed() => match result {
+                        Ok(()) => {
+                            tracing::debug!(hostname = %self.hostname, "Detected IP address change");
+                            ticker.reset();
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                hostname = %self.hostname,
+                                "Failed to watch for IP address changes, falling back to polling: {e:#}"
+                            );
+                            watching = false;
+                            watch_failed = true;
+                            ticker = ticker_after(interval);
+                        }
+                    },
+                }
+            } else {
+                ticker.tick().await;
+            }
+            self.reconcile_with_retry().await?;
+
+            // Sources such as a gateway / router may only detect changes through
+            // some of their protocols, and only know which one works once asked
+            let watches = !watch_failed && self.source.watches_changes();
+            if watches != watching {
+                watching = watches;
+                ticker = ticker_after(if watching {
+                    self.fallback_interval
+                } else {
+                    interval
+                });
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn reconcile_with_retry(&mut self) -> Result<()> {
+        loop {
+            let error = match self.reconcile().await {
+                Ok(_) => {
+                    if let Health::Unhealthy { failures, .. } = &self.health {
+                        tracing::info!(
+                            hostname = %self.hostname,
+                            "Recovered after {failures} failed attempt(s)"
+                        );
+                    }
+                    self.health = Health::Healthy;
+                    self.backoff.reset();
+                    return Ok(());
+                }
+                Err(e) if is_fatal(&e) => return Err(e),
+                Err(e) => e,
+            };
+
+            let delay = self.backoff.next_delay();
+            self.health = Health::Unhealthy {
+                failures: self.backoff.failures(),
+                error: format!("{error:#}"),
+            };
+            tracing::warn!(
+                hostname = %self.hostname,
+                failures = self.backoff.failures(),
+                "Failed to update DNS records, retrying in {:.1}s: {error:#}",
+                delay.as_secs_f64()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn ticker(interval: Duration) -> Interval {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
+
+#[cfg(feature = "tokio")]
+fn ticker_after(interval: Duration) -> Interval {
+    let start = tokio::time::Instant::now() + interval;
+    let mut ticker = tokio::time::interval_at(start, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
@@ -14,6 +14,8 @@ use base64::prelude::*;
     # Example Usage
 
     ```rust
+    use rudder_extractors::BasicAuth;
+
     struct MyAuth {
         email: String,
         password: String,
@@ -1,7 +1,11 @@
 mod basic_auth;
 mod hostname;
 mod ip_variant;
+mod record_attributes;
+mod record_owner;
 
 pub use self::basic_auth::BasicAuth;
 pub use self::hostname::Hostname;
 pub use self::ip_variant::IpVariant;
+pub use self::record_attributes::RecordAttributes;
+pub use self::record_owner::RecordOwner;
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{StatusCode, request::Parts},
+};
+
+/**
+    Attributes for DNS records, extracted from the following optional query parameters:
+
+    - `ttl` - the TTL in seconds, or `auto` (same as `1`) for automatic
+    - `proxied` - one of `true` / `false`, `1` / `0`, or `yes` / `no`
+    - `comment` - the comment for the records
+    - `tags` - the tags for the records, separated by commas
+    - `attributes` - either `preserve` (the default) to keep the attributes of
+      existing records, or `enforce` to change them to the given attributes
+
+    Attributes that are not given are `None` or empty, and the
+    default for the DNS provider should be used for them instead.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordAttributes {
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+    pub comment: Option<String>,
+    pub tags: Vec<String>,
+    /// Whether to change the attributes of existing records, instead of preserving them
+    pub enforce: bool,
+}
+
+impl<S> FromRequestParts<S> for RecordAttributes
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Ok(Query(params)) = Query::<HashMap<String, String>>::try_from_uri(&parts.uri) else {
+            return Ok(Self::default());
+        };
+
+        Ok(Self {
+            ttl: params.get("ttl").map(|v| parse_ttl(v)).transpose()?,
+            proxied: params
+                .get("proxied")
+                .map(|v| parse_proxied(v))
+                .transpose()?,
+            comment: params
+                .get("comment")
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(String::from),
+            tags: params
+                .get("tags")
+                .map(|v| parse_tags(v))
+                .unwrap_or_default(),
+            enforce: params
+                .get("attributes")
+                .map(|v| parse_enforce(v))
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+fn parse_ttl(value: &str) -> Result<u32, (StatusCode, String)> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(1);
+    }
+    match value.parse::<u32>() {
+        Ok(ttl) if ttl == 1 || (30..=86400).contains(&ttl) => Ok(ttl),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "invalid TTL in query parameter 'ttl': must be 'auto', 1, or between 30 and 86400, got '{value}'"
+            ),
+        )),
+    }
+}
+
+fn parse_proxied(value: &str) -> Result<bool, (StatusCode, String)> {
+    let value = value.trim();
+    if ["true", "1", "yes"]
+        .iter()
+        .any(|v| value.eq_ignore_ascii_case(v))
+    {
+        Ok(true)
+    } else if ["false", "0", "no"]
+        .iter()
+        .any(|v| value.eq_ignore_ascii_case(v))
+    {
+        Ok(false)
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            format!("invalid value in query parameter 'proxied': '{value}'"),
+        ))
+    }
+}
+
+fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_enforce(value: &str) -> Result<bool, (StatusCode, String)> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("enforce") {
+        Ok(true)
+    } else if value.eq_ignore_ascii_case("preserve") {
+        Ok(false)
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "invalid value in query parameter 'attributes': must be 'enforce' or 'preserve', got '{value}'"
+            ),
+        ))
+    }
+}
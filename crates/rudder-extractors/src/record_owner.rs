@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{StatusCode, request::Parts},
+};
+
+/**
+    Ownership of DNS records, extracted from the following optional query parameters:
+
+    - `owner` - the owner to mark records with, which can not contain whitespace
+    - `adopt` - one of `true` / `false`, `1` / `0`, or `yes` / `no`, for whether
+      to take over existing records that are not owned by the owner
+
+    When no owner is given, it is `None`, and the default owner should be
+    used instead. Existing records are never adopted unless asked for.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordOwner {
+    pub owner: Option<String>,
+    pub adopt: bool,
+}
+
+impl<S> FromRequestParts<S> for RecordOwner
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Ok(Query(params)) = Query::<HashMap<String, String>>::try_from_uri(&parts.uri) else {
+            return Ok(Self::default());
+        };
+
+        Ok(Self {
+            owner: params.get("owner").map(|v| parse_owner(v)).transpose()?,
+            adopt: params
+                .get("adopt")
+                .map(|v| parse_adopt(v))
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+fn parse_owner(value: &str) -> Result<String, (StatusCode, String)> {
+    let value = value.trim();
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "invalid owner in query parameter 'owner': must be non-empty without whitespace, got '{value}'"
+            ),
+        ));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_adopt(value: &str) -> Result<bool, (StatusCode, String)> {
+    let value = value.trim();
+    if ["true", "1", "yes"]
+        .iter()
+        .any(|v| value.eq_ignore_ascii_case(v))
+    {
+        Ok(true)
+    } else if ["false", "0", "no"]
+        .iter()
+        .any(|v| value.eq_ignore_ascii_case(v))
+    {
+        Ok(false)
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            format!("invalid value in query parameter 'adopt': '{value}'"),
+        ))
+    }
+}
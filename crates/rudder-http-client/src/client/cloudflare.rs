@@ -1,18 +1,193 @@
-use std::sync::Arc;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    marker::PhantomData,
+    sync::Arc,
+};
 
-use anyhow::{Context, Result, bail};
-use reqwest::header::AUTHORIZATION;
+use anyhow::{Context, Result};
+use http::header::AUTHORIZATION;
+use serde::de::{DeserializeOwned, IgnoredAny};
 
 use crate::{
     models::cloudflare::{
-        CloudflareDnsRecord, CloudflareUserToken, CloudflareUserTokenStatus, CloudflareZone,
+        CloudflareDnsRecord, CloudflareDnsRecordFilter, CloudflarePage, CloudflareUserToken,
+        CloudflareUserTokenStatus, CloudflareZone,
     },
-    private::cloudflare::CloudflareResponse,
+    private::{cloudflare::CloudflareResponse, http::HttpClient},
 };
 
+/// The maximum number of zones per page allowed by the API
+const ZONES_PER_PAGE: u32 = 50;
+
+/// The number of DNS records to fetch per page
+const DNS_RECORDS_PER_PAGE: u32 = 100;
+
+/**
+    Codes for errors that retrying a request will never fix, such as
+    invalid credentials, missing permissions, or a zone that does not exist.
+*/
+const PERMANENT_ERROR_CODES: &[u32] = &[
+    1000,  // Invalid API token
+    1001,  // Invalid zone identifier
+    1003,  // Invalid or missing zone id
+    6003,  // Invalid request headers
+    6111,  // Invalid format for Authorization header
+    7003,  // Could not route, perhaps the object identifier is invalid
+    9103,  // Unknown auth key or email
+    9109,  // Invalid access token
+    10000, // Authentication error
+];
+
+/**
+    Codes for errors caused by the API token itself, being invalid, malformed, or unknown.
+*/
+const AUTH_ERROR_CODES: &[u32] = &[
+    1000,  // Invalid API token
+    6003,  // Invalid request headers
+    6111,  // Invalid format for Authorization header
+    9103,  // Unknown auth key or email
+    9109,  // Invalid access token
+    10000, // Authentication error
+];
+
+/**
+    An error returned by the Cloudflare API, either as error
+    codes in a response, or as a token that is not active.
+
+    This is the root cause of the [`anyhow::Error`] returned by
+    [`CloudflareClient`] methods when the API rejects a request,
+    and can be found through [`anyhow::Error::chain`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloudflareApiError {
+    /// The API responded with errors, identified by their codes
+    Response { codes: Vec<u32> },
+    /// The API token exists, but is disabled or expired
+    InactiveToken { status: CloudflareUserTokenStatus },
+}
+
+impl CloudflareApiError {
+    /**
+        Checks if this error can never be fixed by retrying the request,
+        such as when the API token is invalid, or the zone does not exist.
+    */
+    #[must_use]
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::Response { codes } => codes.iter().any(|c| PERMANENT_ERROR_CODES.contains(c)),
+            Self::InactiveToken { .. } => true,
+        }
+    }
+
+    /**
+        Checks if this error was caused by the API token, such as when it
+        is invalid, or disabled, as opposed to the request or its contents.
+    */
+    #[must_use]
+    pub fn is_auth(&self) -> bool {
+        match self {
+            Self::Response { codes } => codes.iter().any(|c| AUTH_ERROR_CODES.contains(c)),
+            Self::InactiveToken { .. } => true,
+        }
+    }
+}
+
+impl Display for CloudflareApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Response { .. } => "cloudflare API error".fmt(f),
+            Self::InactiveToken { status } => write!(f, "token status is {status:?}"),
+        }
+    }
+}
+
+impl Error for CloudflareApiError {}
+
+/**
+    A paginated listing from the Cloudflare API, which fetches
+    one page at a time, only when the next page is requested.
+*/
+#[derive(Debug, Clone)]
+pub struct CloudflarePages<T> {
+    client: CloudflareClient,
+    url: String,
+    query: Vec<(&'static str, String)>,
+    what: &'static str,
+    next_page: Option<u32>,
+    _items: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> CloudflarePages<T> {
+    fn new(
+        client: CloudflareClient,
+        url: String,
+        query: Vec<(&'static str, String)>,
+        what: &'static str,
+    ) -> Self {
+        Self {
+            client,
+            url,
+            query,
+            what,
+            next_page: Some(1),
+            _items: PhantomData,
+        }
+    }
+
+    /**
+        Fetches the next page, or returns `None` once all pages have been fetched.
+    */
+    pub async fn next_page(&mut self) -> Result<Option<Vec<T>>> {
+        let Some(page) = self.next_page else {
+            return Ok(None);
+        };
+        let page = self.fetch(page).await?;
+        self.next_page = page
+            .has_next()
+            .then(|| page.info.map_or(1, |info| info.page) + 1);
+        Ok(Some(page.items))
+    }
+
+    /**
+        Fetches all of the remaining pages, and collects their items.
+    */
+    pub async fn collect(mut self) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            items.extend(page);
+        }
+        Ok(items)
+    }
+
+    async fn fetch(&self, page: u32) -> Result<CloudflarePage<T>> {
+        let page = page.to_string();
+        let mut query = self
+            .query
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect::<Vec<_>>();
+        query.push(("page", &page));
+        let request = self
+            .client
+            .inner
+            .get(&self.url)
+            .query(&query)
+            .header(AUTHORIZATION, self.client.api_token.as_ref());
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("{} failed", self.what))?;
+        response
+            .json::<CloudflareResponse<Vec<T>>>()
+            .with_context(|| format!("{} response failure", self.what))?
+            .into_page()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CloudflareClient {
-    pub(crate) inner: reqwest::Client,
+    pub(crate) inner: HttpClient,
     pub(crate) api_token: Arc<str>,
 }
 
@@ -28,47 +203,90 @@ impl CloudflareClient {
             .context("verifying token for account failed")?;
         let token = response
             .json::<CloudflareResponse<CloudflareUserToken>>()
-            .await
             .context("verifying token for account response failure")?
             .into_result()?;
         if !matches!(token.status, CloudflareUserTokenStatus::Active) {
-            bail!("token status is {:?}", token.status)
+            return Err(CloudflareApiError::InactiveToken {
+                status: token.status,
+            }
+            .into());
         }
         Ok(())
     }
 
+    /**
+        Lists all zones that the API token is assigned to, fetching every page.
+    */
     pub async fn list_zones(&self) -> Result<Vec<CloudflareZone>> {
-        let request = self
-            .inner
-            .get("https://api.cloudflare.com/client/v4/zones")
-            .header(AUTHORIZATION, self.api_token.as_ref());
-        let response = request
-            .send()
-            .await
-            .context("listing zones for account failed")?;
-        response
-            .json::<CloudflareResponse<_>>()
-            .await
-            .context("listing zones for account response failure")?
-            .into_result()
+        self.zones().collect().await
     }
 
-    pub async fn list_dns_records(&self, zone_id: &str) -> Result<Vec<CloudflareDnsRecord>> {
-        let request = self
-            .inner
-            .get(format!(
-                "https://api.cloudflare.com/client/v4/zones/{zone_id}/dns_records"
-            ))
-            .header(AUTHORIZATION, self.api_token.as_ref());
-        let response = request
-            .send()
-            .await
-            .context("listing zones for account failed")?;
-        response
-            .json::<CloudflareResponse<_>>()
-            .await
-            .context("listing zones for account response failure")?
-            .into_result()
+    /**
+        Lists a single page of the zones that the API token is assigned to, starting at page 1.
+    */
+    pub async fn list_zones_page(&self, page: u32) -> Result<CloudflarePage<CloudflareZone>> {
+        self.zones().fetch(page).await
+    }
+
+    /**
+        Lists the zones that the API token is assigned to, one page at a time.
+    */
+    #[must_use]
+    pub fn zones(&self) -> CloudflarePages<CloudflareZone> {
+        CloudflarePages::new(
+            self.clone(),
+            "https://api.cloudflare.com/client/v4/zones".to_string(),
+            vec![("per_page", ZONES_PER_PAGE.to_string())],
+            "listing zones for account",
+        )
+    }
+
+    /**
+        Lists all DNS records in the given zone that match the given filter, fetching every page.
+    */
+    pub async fn list_dns_records(
+        &self,
+        zone_id: &str,
+        filter: &CloudflareDnsRecordFilter,
+    ) -> Result<Vec<CloudflareDnsRecord>> {
+        self.dns_records(zone_id, filter).collect().await
+    }
+
+    /**
+        Lists a single page of the DNS records in the given zone
+        that match the given filter, starting at page 1.
+    */
+    pub async fn list_dns_records_page(
+        &self,
+        zone_id: &str,
+        filter: &CloudflareDnsRecordFilter,
+        page: u32,
+    ) -> Result<CloudflarePage<CloudflareDnsRecord>> {
+        self.dns_records(zone_id, filter).fetch(page).await
+    }
+
+    /**
+        Lists the DNS records in the given zone that match the given filter, one page at a time.
+    */
+    #[must_use]
+    pub fn dns_records(
+        &self,
+        zone_id: &str,
+        filter: &CloudflareDnsRecordFilter,
+    ) -> CloudflarePages<CloudflareDnsRecord> {
+        let mut query = vec![("per_page", DNS_RECORDS_PER_PAGE.to_string())];
+        if let Some(name) = &filter.name {
+            query.push(("name", name.clone()));
+        }
+        if let Some(kind) = filter.kind {
+            query.push(("type", kind.to_string()));
+        }
+        CloudflarePages::new(
+            self.clone(),
+            format!("https://api.cloudflare.com/client/v4/zones/{zone_id}/dns_records"),
+            query,
+            "listing dns records for zone",
+        )
     }
 
     pub async fn create_dns_record(
@@ -89,7 +307,6 @@ impl CloudflareClient {
             .context("creating dns record failed")?;
         response
             .json::<CloudflareResponse<_>>()
-            .await
             .context("creating dns record response failure")?
             .into_result()
     }
@@ -113,8 +330,22 @@ impl CloudflareClient {
             .context("updating dns record failed")?;
         response
             .json::<CloudflareResponse<_>>()
-            .await
             .context("updating dns record response failure")?
             .into_result()
     }
+
+    pub async fn delete_dns_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let request = self
+            .inner
+            .delete(format!(
+                "https://api.cloudflare.com/client/v4/zones/{zone_id}/dns_records/{record_id}"
+            ))
+            .header(AUTHORIZATION, self.api_token.as_ref());
+        let response = request.send().await.context("deleting dns record failed")?;
+        response
+            .json::<CloudflareResponse<IgnoredAny>>()
+            .context("deleting dns record response failure")?
+            .into_result()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,43 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    models::ip_api::{IpApiResponse, IpApiStatus},
+    private::http::HttpClient,
+};
+
+#[derive(Debug, Clone)]
+pub struct IpApiClient {
+    pub(crate) inner: HttpClient,
+}
+
+impl IpApiClient {
+    /**
+        Fetches the external IP address of this device using
+        the free API for non-commercial use at `https://ip-api.com`.
+
+        Note that the free API is only available over plain HTTP.
+    */
+    pub async fn get_external_ip(&self) -> Result<IpAddr> {
+        let request = self
+            .inner
+            .get("http://ip-api.com/json/?fields=status,message,query");
+        let response = request
+            .send()
+            .await
+            .context("fetching external ip failed")?;
+        let response = response
+            .json::<IpApiResponse>()
+            .context("fetching external ip response failure")?;
+        if response.status == IpApiStatus::Fail {
+            bail!(
+                "ip-api error: {}",
+                response.message.as_deref().unwrap_or("unknown error")
+            )
+        }
+        response
+            .query
+            .ok_or_else(|| anyhow!("response was successful, but ip is missing"))
+    }
+}
@@ -0,0 +1,38 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, Result, bail};
+use http::header::ACCEPT;
+
+use crate::private::http::HttpClient;
+
+#[derive(Debug, Clone)]
+pub struct IpEchoClient {
+    pub(crate) inner: HttpClient,
+}
+
+impl IpEchoClient {
+    /**
+        Fetches the IP address of this device from a "what is my IP" echo
+        service at the given URL, which must respond with only the address
+        in plain text - such as `https://icanhazip.com`.
+    */
+    pub async fn get_ip(&self, url: &str) -> Result<IpAddr> {
+        let request = self.inner.get(url).header(ACCEPT, "text/plain");
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("fetching ip from '{url}' failed"))?;
+        if !response.status().is_success() {
+            bail!(
+                "fetching ip from '{url}' failed with status {}",
+                response.status()
+            )
+        }
+        let text = response
+            .text()
+            .with_context(|| format!("fetching ip from '{url}' response failure"))?;
+        text.trim()
+            .parse()
+            .with_context(|| format!("response from '{url}' is not a valid ip address"))
+    }
+}
@@ -1,21 +1,41 @@
 #![allow(clippy::missing_panics_doc)]
 #![allow(clippy::missing_errors_doc)]
 
+use std::sync::Arc;
+
 use anyhow::{Result, bail};
-use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
+use http::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
+
+use crate::{private::http::HttpClient, transport::Transport};
 
 mod cloudflare;
+mod ip_api;
+mod ip_echo;
 
-use self::cloudflare::CloudflareClient;
+pub use self::cloudflare::{CloudflareApiError, CloudflareClient, CloudflarePages};
+pub use self::ip_api::IpApiClient;
+pub use self::ip_echo::IpEchoClient;
 
 #[derive(Debug, Clone)]
 pub struct Client {
-    headers: HeaderMap,
+    inner: HttpClient,
 }
 
 impl Client {
+    /**
+        Creates a new client using the default `reqwest` transport.
+    */
     #[must_use]
+    #[cfg(feature = "reqwest")]
     pub fn new() -> Self {
+        Self::with_transport(crate::transport::ReqwestTransport::default())
+    }
+
+    /**
+        Creates a new client that sends all of its requests using the given transport.
+    */
+    #[must_use]
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
         let headers = HeaderMap::from_iter([
             (CONTENT_TYPE, HeaderValue::from_static("application/json")),
             (ACCEPT, HeaderValue::from_static("application/json")),
@@ -28,7 +48,8 @@ impl Client {
                 )),
             ),
         ]);
-        Self { headers }
+        let inner = HttpClient::new(headers, Arc::new(transport));
+        Self { inner }
     }
 
     pub fn cloudflare(&self, api_token: impl AsRef<str>) -> Result<CloudflareClient> {
@@ -39,15 +60,27 @@ impl Client {
 
         let api_token = format!("Bearer {api_token}").into();
 
-        let inner = reqwest::Client::builder()
-            .default_headers(self.headers.clone())
-            .build()
-            .unwrap();
+        let inner = self.inner.clone();
 
         Ok(CloudflareClient { inner, api_token })
     }
+
+    #[must_use]
+    pub fn ip_api(&self) -> IpApiClient {
+        let inner = self.inner.clone();
+
+        IpApiClient { inner }
+    }
+
+    #[must_use]
+    pub fn ip_echo(&self) -> IpEchoClient {
+        let inner = self.inner.clone();
+
+        IpEchoClient { inner }
+    }
 }
 
+#[cfg(feature = "reqwest")]
 impl Default for Client {
     fn default() -> Self {
         Self::new()
@@ -2,5 +2,8 @@ mod client;
 mod private;
 
 pub mod models;
+pub mod transport;
 
-pub use self::client::Client;
+pub use self::client::{
+    Client, CloudflareApiError, CloudflareClient, CloudflarePages, IpApiClient, IpEchoClient,
+};
@@ -1,8 +1,11 @@
-use std::net::IpAddr;
+use std::{
+    fmt::{self, Display},
+    net::IpAddr,
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CloudflareUserTokenStatus {
     Active,
@@ -15,14 +18,84 @@ pub struct CloudflareUserToken {
     pub status: CloudflareUserTokenStatus,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+    Pagination details for a response from a Cloudflare API endpoint that lists items.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloudflareResultInfo {
+    /// The current page, starting at 1
+    #[serde(default)]
+    pub page: u32,
+    /// The maximum number of items per page
+    #[serde(default)]
+    pub per_page: u32,
+    /// The number of items on the current page
+    #[serde(default)]
+    pub count: u32,
+    /// The total number of items across all pages
+    #[serde(default)]
+    pub total_count: u32,
+    /// The total number of pages
+    #[serde(default)]
+    pub total_pages: u32,
+}
+
+/**
+    A single page of items from a Cloudflare API endpoint that lists items.
+*/
+#[derive(Debug, Clone)]
+pub struct CloudflarePage<T> {
+    pub items: Vec<T>,
+    /// The pagination details, if the endpoint returned any
+    pub info: Option<CloudflareResultInfo>,
+}
+
+impl<T> CloudflarePage<T> {
+    /**
+        Checks if there are more pages after this one.
+    */
+    #[must_use]
+    pub fn has_next(&self) -> bool {
+        self.info
+            .is_some_and(|info| info.count > 0 && info.page < info.total_pages)
+    }
+}
+
+/**
+    Server-side filters for listing DNS records, so that
+    only the records that are needed have to be transferred.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CloudflareDnsRecordFilter {
+    /// Only list records with exactly this name
+    pub name: Option<String>,
+    /// Only list records of this kind
+    pub kind: Option<CloudflareDnsRecordKind>,
+}
+
+impl CloudflareDnsRecordFilter {
+    /**
+        Creates a filter for records with the given name and kind.
+    */
+    #[must_use]
+    pub fn new(name: impl Into<String>, kind: CloudflareDnsRecordKind) -> Self {
+        Self {
+            name: Some(name.into()),
+            kind: Some(kind),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CloudflareZone {
     pub id: String,
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub account: CloudflareZoneAccount,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CloudflareZoneAccount {
     pub id: String,
     pub name: String,
@@ -55,6 +128,13 @@ pub enum CloudflareDnsRecordKind {
     URI,
 }
 
+impl Display for CloudflareDnsRecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The debug names match the record types used by the API
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 impl From<IpAddr> for CloudflareDnsRecordKind {
     fn from(ip: IpAddr) -> Self {
         match ip {
@@ -76,6 +156,8 @@ pub struct CloudflareDnsRecord {
     pub content: String,
     #[serde(default)]
     pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     #[serde(default)]
     pub proxied: bool,
     #[serde(default = "default_ttl")]
@@ -90,12 +172,14 @@ impl Default for CloudflareDnsRecord {
             name: String::new(),
             content: String::new(),
             comment: None,
+            tags: Vec::new(),
             proxied: false,
             ttl: default_ttl(),
         }
     }
 }
 
+/// Automatic, which is 300 seconds for records that are not proxied
 fn default_ttl() -> u32 {
-    3600
+    1
 }
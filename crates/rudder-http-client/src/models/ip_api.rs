@@ -0,0 +1,19 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpApiStatus {
+    Success,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpApiResponse {
+    pub status: IpApiStatus,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub query: Option<IpAddr>,
+}
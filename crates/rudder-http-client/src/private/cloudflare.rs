@@ -1,9 +1,14 @@
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use serde::{
     Deserialize, Deserializer,
     de::{DeserializeOwned, Error as SerdeDeError},
 };
 
+use crate::{
+    client::CloudflareApiError,
+    models::cloudflare::{CloudflarePage, CloudflareResultInfo},
+};
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct CloudflareResponseError {
     code: u32,
@@ -26,6 +31,7 @@ impl CloudflareResponseError {
 pub enum CloudflareResponse<T> {
     Success {
         result: T,
+        result_info: Option<CloudflareResultInfo>,
     },
     Error {
         errors: Vec<CloudflareResponseError>,
@@ -35,9 +41,10 @@ pub enum CloudflareResponse<T> {
 impl<T> CloudflareResponse<T> {
     pub fn into_result(self) -> Result<T> {
         match self {
-            CloudflareResponse::Success { result } => Ok(result),
+            CloudflareResponse::Success { result, .. } => Ok(result),
             CloudflareResponse::Error { errors } => {
-                let mut error = anyhow!("cloudflare API error");
+                let codes = errors.iter().map(|e| e.code).collect();
+                let mut error = anyhow::Error::new(CloudflareApiError::Response { codes });
                 for e in errors {
                     error = e.add_context(error);
                 }
@@ -50,6 +57,17 @@ impl<T> CloudflareResponse<T> {
     }
 }
 
+impl<T> CloudflareResponse<Vec<T>> {
+    pub fn into_page(self) -> Result<CloudflarePage<T>> {
+        let info = match &self {
+            CloudflareResponse::Success { result_info, .. } => *result_info,
+            CloudflareResponse::Error { .. } => None,
+        };
+        let items = self.into_result()?;
+        Ok(CloudflarePage { items, info })
+    }
+}
+
 impl<'de, T: DeserializeOwned> Deserialize<'de> for CloudflareResponse<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -60,6 +78,8 @@ impl<'de, T: DeserializeOwned> Deserialize<'de> for CloudflareResponse<T> {
             success: bool,
             result: Option<serde_json::Value>,
             #[serde(default)]
+            result_info: Option<CloudflareResultInfo>,
+            #[serde(default)]
             errors: Vec<CloudflareResponseError>,
         }
 
@@ -77,7 +97,10 @@ impl<'de, T: DeserializeOwned> Deserialize<'de> for CloudflareResponse<T> {
                 if raw.success {
                     match raw.result {
                         Some(value) => match serde_path_to_error::deserialize(value) {
-                            Ok(result) => Ok(CloudflareResponse::Success { result }),
+                            Ok(result) => Ok(CloudflareResponse::Success {
+                                result,
+                                result_info: raw.result_info,
+                            }),
                             Err(err) => Err(SerdeDeError::custom(format!(
                                 "failed to deserialize at '{}': {}",
                                 err.path().clone(),
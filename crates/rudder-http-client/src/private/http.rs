@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::transport::{Transport, TransportResponse};
+
+/**
+    A minimal HTTP client that sends requests using a shared [`Transport`],
+    and attaches a set of default headers to every request.
+*/
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    headers: HeaderMap,
+    transport: Arc<dyn Transport>,
+}
+
+impl HttpClient {
+    pub fn new(headers: HeaderMap, transport: Arc<dyn Transport>) -> Self {
+        Self { headers, transport }
+    }
+
+    pub fn get(&self, url: impl Into<String>) -> HttpRequestBuilder<'_> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl Into<String>) -> HttpRequestBuilder<'_> {
+        self.request(Method::POST, url)
+    }
+
+    pub fn patch(&self, url: impl Into<String>) -> HttpRequestBuilder<'_> {
+        self.request(Method::PATCH, url)
+    }
+
+    pub fn delete(&self, url: impl Into<String>) -> HttpRequestBuilder<'_> {
+        self.request(Method::DELETE, url)
+    }
+
+    fn request(&self, method: Method, url: impl Into<String>) -> HttpRequestBuilder<'_> {
+        HttpRequestBuilder {
+            client: self,
+            method,
+            url: url.into(),
+            headers: self.headers.clone(),
+            body: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+pub struct HttpRequestBuilder<'a> {
+    client: &'a HttpClient,
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    error: Option<anyhow::Error>,
+}
+
+impl HttpRequestBuilder<'_> {
+    pub fn header(mut self, name: HeaderName, value: &str) -> Self {
+        match HeaderValue::from_str(value) {
+            Ok(value) => {
+                self.headers.insert(name, value);
+            }
+            Err(err) => {
+                self.error.get_or_insert_with(|| {
+                    anyhow::Error::new(err).context(format!("invalid value for header '{name}'"))
+                });
+            }
+        }
+        self
+    }
+
+    pub fn query(mut self, params: &[(&str, &str)]) -> Self {
+        for (name, value) in params {
+            self.url
+                .push(if self.url.contains('?') { '&' } else { '?' });
+            self.url.push_str(&encode_query_component(name));
+            self.url.push('=');
+            self.url.push_str(&encode_query_component(value));
+        }
+        self
+    }
+
+    pub fn json<T: Serialize>(mut self, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => self.body = body,
+            Err(err) => {
+                self.error.get_or_insert_with(|| {
+                    anyhow::Error::new(err).context("failed to serialize request body")
+                });
+            }
+        }
+        self
+    }
+
+    pub async fn send(self) -> Result<HttpResponse> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let mut request = http::Request::builder()
+            .method(self.method)
+            .uri(self.url)
+            .body(self.body)?;
+        *request.headers_mut() = self.headers;
+
+        let inner = self.client.transport.send(request).await?;
+        Ok(HttpResponse { inner })
+    }
+}
+
+pub struct HttpResponse {
+    inner: TransportResponse,
+}
+
+impl HttpResponse {
+    pub fn status(&self) -> StatusCode {
+        self.inner.status()
+    }
+
+    pub fn text(&self) -> Result<&str> {
+        std::str::from_utf8(self.inner.body())
+            .with_context(|| format!("response contained invalid utf8 (status {})", self.status()))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(self.inner.body())
+            .with_context(|| format!("failed to parse response (status {})", self.status()))
+    }
+}
+
+/// Percent-encodes everything except unreserved characters, for use in query strings
+fn encode_query_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            const HEX: &[u8; 16] = b"0123456789ABCDEF";
+            encoded.push('%');
+            encoded.push(char::from(HEX[usize::from(byte >> 4)]));
+            encoded.push(char::from(HEX[usize::from(byte & 0xF)]));
+        }
+    }
+    encoded
+}
@@ -1 +1,2 @@
 pub mod cloudflare;
+pub mod http;
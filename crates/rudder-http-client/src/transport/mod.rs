@@ -0,0 +1,31 @@
+use std::fmt::Debug;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+#[cfg(feature = "reqwest")]
+mod reqwest;
+
+#[cfg(feature = "reqwest")]
+pub use self::reqwest::ReqwestTransport;
+
+/// An outgoing HTTP request, with a fully buffered body
+pub type TransportRequest = http::Request<Vec<u8>>;
+
+/// An incoming HTTP response, with a fully buffered body
+pub type TransportResponse = http::Response<Vec<u8>>;
+
+/**
+    A transport that is able to send HTTP requests and receive responses.
+
+    Every API client created by a [`Client`](crate::Client) sends its requests
+    through a transport, which makes it possible to use the same clients in
+    environments where native sockets are not available, such as WebAssembly.
+
+    The default transport uses `reqwest`, and is only available when
+    the `reqwest` feature of this crate is enabled (on by default).
+*/
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse>;
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{Transport, TransportRequest, TransportResponse};
+
+/**
+    The default [`Transport`], using a native `reqwest` client.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    inner: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    #[must_use]
+    pub fn new(inner: reqwest::Client) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let request = reqwest::Request::try_from(request)?;
+        let response = self.inner.execute(request).await?;
+
+        let mut builder = http::Response::builder()
+            .status(response.status())
+            .version(response.version());
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(response.headers().clone());
+        }
+
+        let body = response.bytes().await?;
+        Ok(builder.body(body.to_vec())?)
+    }
+}
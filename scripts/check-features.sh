@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+
+# This script lints a crate with no features, and then with each of its
+# features on its own, so that features never depend on other features
+# being turned on by another crate in the workspace without declaring it
+
+set -e
+
+# We should have gotten the package name as the first arg to this script
+PACKAGE="$1"
+if [ -z "$PACKAGE" ]; then
+    echo "Usage: $0 <PACKAGE>"
+    exit 1
+fi
+
+# Read all features of the package from its manifest, except for the default set
+FEATURES=$(cargo metadata --no-deps --format-version 1 \
+    | jq -r --arg pkg "$PACKAGE" '.packages[] | select(.name == $pkg) | .features | keys[] | select(. != "default")')
+
+echo "Checking '$PACKAGE' with no features"
+cargo clippy -p "$PACKAGE" --all-targets --no-default-features -- -D warnings
+
+for FEATURE in $FEATURES; do
+    echo "Checking '$PACKAGE' with feature '$FEATURE'"
+    cargo clippy -p "$PACKAGE" --all-targets --no-default-features --features "$FEATURE" -- -D warnings
+done